@@ -0,0 +1,61 @@
+//! Throughput of `press_input`, which every button press and release goes through.
+//!
+//! The remaps log their input instead of injecting it, so the desktop is left
+//! alone and the time is spent resolving and firing the bindings.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+
+const CONFIG: &str = r#"
+alternative_activator = 'left_bumper'
+chord_activator = ['left_thumb', 'right_thumb']
+
+[main]
+south = { seq = ['Space'] }
+dpad_up = { repeat = 'UpArrow' }
+
+[alt]
+south = { seq = ['Return'] }
+
+[layer_activators]
+fn = 'right_bumper'
+
+[layers.fn]
+west = { seq = ['F1'] }
+"#;
+
+fn bench_press_input(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    assert!(runtime.block_on(dogmu::bench::load_config(CONFIG)));
+
+    let mut group = c.benchmark_group("press_input");
+    group.bench_function("seq", |b| {
+        b.to_async(&runtime).iter(|| async {
+            dogmu::bench::press_input(0, "south", true).await;
+            dogmu::bench::press_input(0, "south", false).await;
+        })
+    });
+    group.bench_function("unmapped", |b| {
+        b.to_async(&runtime).iter(|| async {
+            dogmu::bench::press_input(0, "north", true).await;
+            dogmu::bench::press_input(0, "north", false).await;
+        })
+    });
+    group.bench_function("alt", |b| {
+        b.to_async(&runtime).iter(|| async {
+            dogmu::bench::press_input(0, "left_bumper", true).await;
+            dogmu::bench::press_input(0, "south", true).await;
+            dogmu::bench::press_input(0, "south", false).await;
+            dogmu::bench::press_input(0, "left_bumper", false).await;
+        })
+    });
+    group.bench_function("repeat", |b| {
+        b.to_async(&runtime).iter(|| async {
+            dogmu::bench::press_input(0, "dpad_up", true).await;
+            dogmu::bench::press_input(0, "dpad_up", false).await;
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_press_input);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+#[cfg(all(target_os = "windows", not(test)))]
+#[link(name = "user32")]
+unsafe extern "system" {
+    fn MessageBoxW(hwnd: *mut std::ffi::c_void, text: *const u16, caption: *const u16, u_type: u32) -> i32;
+}
+
+/// Reports an error the user should act on.
+///
+/// The error is logged, and on Windows also shown in a message box, since the
+/// application usually runs without a console there.
+pub fn show_error(message: &str) {
+    log::error!("{message}");
+
+    #[cfg(all(target_os = "windows", not(test)))]
+    {
+        const MB_ICONERROR: u32 = 0x10;
+
+        let to_wide = |text: &str| text.encode_utf16().chain([0]).collect::<Vec<_>>();
+        let text = to_wide(message);
+        let caption = to_wide(env!("CARGO_PKG_NAME"));
+        unsafe {
+            MessageBoxW(std::ptr::null_mut(), text.as_ptr(), caption.as_ptr(), MB_ICONERROR);
+        }
+    }
+}
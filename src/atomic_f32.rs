@@ -4,6 +4,16 @@ use std::sync::atomic::{AtomicU32, Ordering};
 ///
 /// This struct allows for atomic operations on floating-point numbers
 /// by internally representing the `f32` value as a `u32` using bitwise operations.
+///
+/// Only finite values are stored: NaN and infinities become zero, so that a
+/// driver reporting them, e.g. on disconnect, cannot spread them through the
+/// stick math.
+///
+/// The operations use `Ordering::Relaxed` unless their `_with` variant is
+/// called: a relaxed value is always read whole, but orders nothing around it,
+/// which suits a value written by one task and polled by another. To publish
+/// other data along with the value, store it with `Release` and load it with
+/// `Acquire`.
 pub struct AtomicF32 {
     storage: AtomicU32,
 }
@@ -16,13 +26,27 @@ impl AtomicF32 {
         }
     }
 
-    /// Atomically stores an `f32` value.
+    /// Atomically stores an `f32` value, or zero if it is not finite.
     ///
     /// # Arguments
     ///
     /// * `value` - The `f32` value to store atomically.
     pub fn store(&self, value: f32) {
-        self.storage.store(value.to_bits(), Ordering::Relaxed);
+        self.store_with(value, Ordering::Relaxed);
+    }
+
+    /// Atomically stores an `f32` value, or zero if it is not finite, with a given memory ordering.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The `f32` value to store atomically.
+    /// * `ordering` - The memory ordering of the store.
+    ///
+    /// # Panics
+    ///
+    /// If `ordering` is `Acquire` or `AcqRel`, which do not apply to stores.
+    pub fn store_with(&self, value: f32, ordering: Ordering) {
+        self.storage.store(finite_bits(value), ordering);
     }
 
     /// Atomically loads the current `f32` value.
@@ -31,11 +55,182 @@ impl AtomicF32 {
     ///
     /// The current `f32` value stored atomically.
     pub fn load(&self) -> f32 {
-        f32::from_bits(self.storage.load(Ordering::Relaxed))
+        self.load_with(Ordering::Relaxed)
+    }
+
+    /// Atomically loads the current `f32` value with a given memory ordering.
+    ///
+    /// # Arguments
+    ///
+    /// * `ordering` - The memory ordering of the load.
+    ///
+    /// # Returns
+    ///
+    /// The current `f32` value stored atomically.
+    ///
+    /// # Panics
+    ///
+    /// If `ordering` is `Release` or `AcqRel`, which do not apply to loads.
+    pub fn load_with(&self, ordering: Ordering) -> f32 {
+        f32::from_bits(self.storage.load(ordering))
     }
 
     /// Resets the `AtomicF32` value to zero atomically.
     pub fn reset(&self) {
         self.storage.store(0, Ordering::Relaxed);
     }
+
+    /// Atomically adds to the current `f32` value, resetting it to zero if the sum is not finite.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The `f32` value to add.
+    pub fn add(&self, value: f32) {
+        self.fetch_add(value);
+    }
+
+    /// Atomically adds to the current `f32` value, returning the previous one.
+    ///
+    /// The sum is computed in a compare-exchange loop, retried whenever another
+    /// thread changed the value in between, so no addition is ever lost. Like
+    /// `store`, a sum that is not finite is stored as zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `delta` - The `f32` value to add.
+    ///
+    /// # Returns
+    ///
+    /// The `f32` value stored before the addition.
+    pub fn fetch_add(&self, delta: f32) -> f32 {
+        let mut bits = self.storage.load(Ordering::Relaxed);
+
+        loop {
+            let sum = finite_bits(f32::from_bits(bits) + delta);
+            match self
+                .storage
+                .compare_exchange_weak(bits, sum, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(previous) => return f32::from_bits(previous),
+                Err(current) => bits = current,
+            }
+        }
+    }
+
+    /// Atomically replaces the value, returning the previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The `f32` value to store, or zero if it is not finite.
+    ///
+    /// # Returns
+    ///
+    /// The `f32` value stored before the swap.
+    pub fn swap(&self, value: f32) -> f32 {
+        f32::from_bits(self.storage.swap(finite_bits(value), Ordering::Relaxed))
+    }
+
+    /// Atomically resets the value to zero, returning the previous one.
+    ///
+    /// # Returns
+    ///
+    /// The `f32` value stored before the reset.
+    pub fn take(&self) -> f32 {
+        self.swap(0.)
+    }
+}
+
+/// Returns the bits of a value to store, those of zero if it is not finite.
+pub fn finite_bits(value: f32) -> u32 {
+    if value.is_finite() { value.to_bits() } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_finite_values() {
+        let value = AtomicF32::new();
+
+        for non_finite in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            value.store(0.5);
+            value.store(non_finite);
+            assert_eq!(value.load(), 0.);
+        }
+
+        value.store(0.5);
+        value.add(f32::NAN);
+        assert_eq!(value.load(), 0.);
+        value.store(f32::MAX);
+        value.add(f32::MAX);
+        assert_eq!(value.load(), 0.);
+
+        // Finite values are kept as is, negative zero included
+        value.store(-0.);
+        assert!(value.load().is_sign_negative());
+        value.store(0.25);
+        value.add(0.5);
+        assert_eq!(value.load(), 0.75);
+    }
+
+    #[test]
+    fn test_orderings() {
+        let value = AtomicF32::new();
+        let is_ready = std::sync::atomic::AtomicBool::new(false);
+
+        // A value released along with a flag is seen whole once the flag is acquired
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                value.store_with(0.75, Ordering::Relaxed);
+                is_ready.store(true, Ordering::Release);
+            });
+            scope.spawn(|| {
+                while !is_ready.load(Ordering::Acquire) {
+                    std::hint::spin_loop();
+                }
+                assert_eq!(value.load_with(Ordering::Relaxed), 0.75);
+            });
+        });
+
+        value.store_with(0.5, Ordering::SeqCst);
+        assert_eq!(value.load_with(Ordering::Acquire), 0.5);
+        assert_eq!(value.load(), 0.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_acquire_store() {
+        AtomicF32::new().store_with(0.5, Ordering::Acquire);
+    }
+
+    #[test]
+    fn test_swap_and_fetch_add() {
+        let value = AtomicF32::new();
+
+        assert_eq!(value.swap(1.5), 0.);
+        assert_eq!(value.fetch_add(-0.5), 1.5);
+        assert_eq!(value.load(), 1.);
+        assert_eq!(value.swap(f32::NAN), 1.);
+        assert_eq!(value.take(), 0.);
+    }
+
+    #[test]
+    fn test_concurrent_fetch_add() {
+        const THREADS: usize = 8;
+        const ADDS: usize = 10_000;
+        let value = AtomicF32::new();
+
+        // Halves add up exactly, so any lost addition shows in the total
+        std::thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..ADDS {
+                        value.fetch_add(0.5);
+                    }
+                });
+            }
+        });
+        assert_eq!(value.load(), (THREADS * ADDS) as f32 * 0.5);
+    }
 }
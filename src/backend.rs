@@ -0,0 +1,268 @@
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Mutex};
+
+use enigo::{Axis, Button, Coordinate, Direction, InputResult, Key, Keyboard, Mouse};
+
+/// A destination for the keyboard and mouse input produced by remaps.
+///
+/// `Enigo` is the backend injecting input into the system. Anything else
+/// implementing enigo's `Keyboard` and `Mouse` traits can replace it.
+pub trait Backend: Keyboard + Mouse + Send {}
+
+impl<T: Keyboard + Mouse + Send> Backend for T {}
+
+/// An input for the worker owning the backend.
+#[derive(Debug)]
+pub enum InputCommand {
+    Key(Key, Direction),
+    Button(Button, Direction),
+    MoveMouse(i32, i32, Coordinate),
+    Scroll(i32, Axis),
+    Text(String),
+    /// Measures the size of the main display again.
+    RefreshMainDisplay,
+    /// Replies once the inputs sent before are injected.
+    Flush(Sender<()>),
+}
+
+/// Sends inputs to a worker thread owning the backend, which injects them in order.
+///
+/// Callers only queue their inputs, so a remap injecting many of them never
+/// makes the stick loops wait for the backend.
+pub struct InputSender {
+    sender: Sender<Vec<InputCommand>>,
+    /// The size of the main display, as last measured by the worker.
+    main_display: Arc<Mutex<InputResult<(i32, i32)>>>,
+}
+
+impl InputSender {
+    /// Starts the worker thread owning a backend.
+    pub fn spawn(mut backend: Box<dyn Backend>) -> Self {
+        let (sender, receiver) = channel::<Vec<InputCommand>>();
+        let main_display = Arc::new(Mutex::new(backend.main_display()));
+
+        let worker_main_display = main_display.clone();
+
+        std::thread::spawn(move || {
+            for command in receiver.into_iter().flatten() {
+                let result = match command {
+                    InputCommand::Key(key, direction) => backend.key(key, direction),
+                    InputCommand::Button(button, direction) => backend.button(button, direction),
+                    InputCommand::MoveMouse(x, y, coordinate) => backend.move_mouse(x, y, coordinate),
+                    InputCommand::Scroll(length, axis) => backend.scroll(length, axis),
+                    InputCommand::Text(ref text) => backend.text(text),
+                    InputCommand::RefreshMainDisplay => {
+                        *worker_main_display.lock().unwrap() = backend.main_display();
+                        continue;
+                    }
+                    InputCommand::Flush(reply) => {
+                        let _ = reply.send(());
+                        continue;
+                    }
+                };
+                // An input the system rejects is not worth stopping every other one
+                if let Err(err) = result {
+                    log::error!("Failed to inject {command:?}: {err}");
+                }
+            }
+        });
+        Self { sender, main_display }
+    }
+
+    /// Queues inputs, injected one after the other without inputs of other callers in between.
+    pub fn send(&self, commands: impl IntoIterator<Item = InputCommand>) {
+        // The worker only stops with the process
+        let _ = self.sender.send(commands.into_iter().collect());
+    }
+
+    /// Returns the size of the main display as last measured.
+    ///
+    /// The stick loops read it every poll, so they never wait for the worker.
+    pub fn main_display(&self) -> InputResult<(i32, i32)> {
+        self.main_display.lock().unwrap().clone()
+    }
+
+    /// Has the worker measure the main display again, after the inputs queued before.
+    pub fn refresh_main_display(&self) {
+        self.send([InputCommand::RefreshMainDisplay]);
+    }
+
+    /// Waits for the queued inputs to be injected, e.g. before exiting.
+    pub fn flush(&self) {
+        let (reply, receiver) = channel();
+        self.send([InputCommand::Flush(reply)]);
+        let _ = receiver.recv();
+    }
+}
+
+/// Creates the backend injecting input into the system with the given settings, or logging it in a dry run.
+#[cfg(not(test))]
+pub fn new_backend(is_dry_run: bool, settings: &enigo::Settings) -> Box<dyn Backend> {
+    if is_dry_run {
+        Box::new(DryRunBackend)
+    } else {
+        Box::new(enigo::Enigo::new(settings).expect("Failed to initialize Enigo"))
+    }
+}
+
+/// Creates a backend recording its input, so tests never touch the desktop.
+#[cfg(test)]
+pub fn new_backend(_is_dry_run: bool, _settings: &enigo::Settings) -> Box<dyn Backend> {
+    Box::new(mock::MockBackend)
+}
+
+/// Explains why the input injected on Linux may be ignored by the session, from its
+/// `XDG_SESSION_TYPE` and `XDG_CURRENT_DESKTOP`.
+///
+/// enigo is only built with its Wayland backend, which injects through the virtual keyboard
+/// and virtual pointer protocols of wlroots compositors and the fake input protocol of KDE.
+/// An X11 session offers none of them, and neither does GNOME.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn session_warning(session_type: Option<&str>, desktop: Option<&str>) -> Option<String> {
+    let hint = "log into a Wayland session of a compositor supporting them, e.g. Sway, Hyprland or KDE Plasma";
+    match session_type.map(str::to_lowercase).as_deref() {
+        Some("x11") => Some(format!("X11 session, but input is only injected through Wayland protocols: {hint}")),
+        Some("wayland")
+            if desktop.is_some_and(|desktop| desktop.split(':').any(|name| name.eq_ignore_ascii_case("gnome"))) =>
+        {
+            Some(format!("GNOME does not offer the Wayland protocols injecting input: {hint}"))
+        }
+        _ => None,
+    }
+}
+
+/// A backend logging the input it receives instead of injecting it.
+#[cfg_attr(test, allow(dead_code))]
+pub struct DryRunBackend;
+
+impl Keyboard for DryRunBackend {
+    fn fast_text(&mut self, text: &str) -> InputResult<Option<()>> {
+        log::info!("Type {text:?}");
+        Ok(Some(()))
+    }
+
+    fn key(&mut self, key: Key, direction: Direction) -> InputResult<()> {
+        log::info!("Key {key:?} {direction:?}");
+        Ok(())
+    }
+
+    fn raw(&mut self, keycode: u16, direction: Direction) -> InputResult<()> {
+        log::info!("Raw key {keycode} {direction:?}");
+        Ok(())
+    }
+}
+
+impl Mouse for DryRunBackend {
+    fn button(&mut self, button: Button, direction: Direction) -> InputResult<()> {
+        log::info!("Mouse button {button:?} {direction:?}");
+        Ok(())
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> InputResult<()> {
+        log::info!("Move mouse by ({x}, {y}) {coordinate:?}");
+        Ok(())
+    }
+
+    fn scroll(&mut self, length: i32, axis: Axis) -> InputResult<()> {
+        log::info!("Scroll {length} {axis:?}");
+        Ok(())
+    }
+
+    fn main_display(&self) -> InputResult<(i32, i32)> {
+        Ok((1920, 1080))
+    }
+
+    fn location(&self) -> InputResult<(i32, i32)> {
+        Ok((0, 0))
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::sync::Mutex;
+
+    use enigo::{Axis, Button, Coordinate, Direction, InputError, InputResult, Key, Keyboard, Mouse};
+
+    /// An input received by the mock backend.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Output {
+        Key(Key, Direction),
+        Button(Button, Direction),
+        MoveMouse(i32, i32, Coordinate),
+        Scroll(i32, Axis),
+        Text(String),
+    }
+
+    static OUTPUTS: Mutex<Vec<Output>> = Mutex::new(Vec::new());
+    static MAIN_DISPLAY: Mutex<Option<(i32, i32)>> = Mutex::new(Some((1920, 1080)));
+
+    /// Returns the inputs received since the last call.
+    pub fn take_outputs() -> Vec<Output> {
+        std::mem::take(&mut OUTPUTS.lock().unwrap())
+    }
+
+    /// Sets the size of the main display, or `None` to fail getting it as on Wayland.
+    pub fn set_main_display(size: Option<(i32, i32)>) {
+        *MAIN_DISPLAY.lock().unwrap() = size;
+    }
+
+    fn record(output: Output) -> InputResult<()> {
+        OUTPUTS.lock().unwrap().push(output);
+        Ok(())
+    }
+
+    /// A backend recording every input it receives.
+    pub struct MockBackend;
+
+    impl Keyboard for MockBackend {
+        fn fast_text(&mut self, text: &str) -> InputResult<Option<()>> {
+            record(Output::Text(text.to_string())).map(Some)
+        }
+
+        fn key(&mut self, key: Key, direction: Direction) -> InputResult<()> {
+            record(Output::Key(key, direction))
+        }
+
+        fn raw(&mut self, keycode: u16, direction: Direction) -> InputResult<()> {
+            record(Output::Key(Key::Other(keycode.into()), direction))
+        }
+    }
+
+    impl Mouse for MockBackend {
+        fn button(&mut self, button: Button, direction: Direction) -> InputResult<()> {
+            record(Output::Button(button, direction))
+        }
+
+        fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> InputResult<()> {
+            record(Output::MoveMouse(x, y, coordinate))
+        }
+
+        fn scroll(&mut self, length: i32, axis: Axis) -> InputResult<()> {
+            record(Output::Scroll(length, axis))
+        }
+
+        fn main_display(&self) -> InputResult<(i32, i32)> {
+            MAIN_DISPLAY.lock().unwrap().ok_or(InputError::Simulate("Not implemented yet"))
+        }
+
+        fn location(&self) -> InputResult<(i32, i32)> {
+            Ok((0, 0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_warning() {
+        assert_eq!(session_warning(None, None), None);
+        assert_eq!(session_warning(Some("wayland"), Some("sway")), None);
+        assert_eq!(session_warning(Some("wayland"), Some("KDE")), None);
+        assert!(session_warning(Some("x11"), Some("KDE")).unwrap().starts_with("X11 session"));
+        assert!(session_warning(Some("wayland"), Some("ubuntu:GNOME")).unwrap().starts_with("GNOME"));
+        // Only the Wayland session of GNOME lacks the protocols, its X11 session is reported as such
+        assert!(session_warning(Some("x11"), Some("GNOME")).unwrap().starts_with("X11 session"));
+    }
+}
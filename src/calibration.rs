@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+/// How long the sticks are sampled by `--calibrate`.
+pub const CALIBRATION_DURATION: Duration = Duration::from_secs(3);
+
+/// Interval between two samples of the sticks.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Margin added to the spread of the resting positions in the suggested dead zones.
+const DEAD_ZONE_MARGIN: f32 = 0.02;
+
+/// Positions reported by a stick left at rest.
+#[derive(Debug, Default)]
+pub struct StickSamples {
+    positions: Vec<(f32, f32)>,
+}
+
+impl StickSamples {
+    pub fn add(&mut self, x: f32, y: f32) {
+        self.positions.push((x, y));
+    }
+
+    /// Returns the average resting position, to subtract from the stick values.
+    pub fn center(&self) -> (f32, f32) {
+        if self.positions.is_empty() {
+            return (0., 0.);
+        }
+
+        let count = self.positions.len() as f32;
+        let (sum_x, sum_y) = self
+            .positions
+            .iter()
+            .fold((0., 0.), |(sum_x, sum_y), (x, y)| (sum_x + x, sum_y + y));
+        (sum_x / count, sum_y / count)
+    }
+
+    /// Returns a dead zone covering every resting position once centered, with a
+    /// margin, rounded up to a hundredth.
+    pub fn dead_zone(&self) -> f32 {
+        let (center_x, center_y) = self.center();
+        let spread = self
+            .positions
+            .iter()
+            .map(|(x, y)| ((x - center_x).powi(2) + (y - center_y).powi(2)).sqrt())
+            .fold(0., f32::max);
+
+        ((spread + DEAD_ZONE_MARGIN) * 100.).ceil() / 100.
+    }
+}
+
+/// Formats the config options correcting the drift measured on both sticks.
+pub fn suggested_config(left: &StickSamples, right: &StickSamples) -> String {
+    [("left_stick", left), ("right_stick", right)]
+        .into_iter()
+        .map(|(prefix, samples)| {
+            let (x, y) = samples.center();
+            format!(
+                "{prefix}_center_x = {x:.3}\n{prefix}_center_y = {y:.3}\n{prefix}_dead_zone = {:.2}\n",
+                samples.dead_zone()
+            )
+        })
+        .collect()
+}
+
+/// Samples the sticks of the first connected controller while they are left at rest.
+///
+/// # Returns
+///
+/// The config options correcting their drift, or an error if no controller is connected.
+pub fn calibrate(duration: Duration) -> Result<String, String> {
+    let mut gilrs = gilrs::Gilrs::new().map_err(|err| err.to_string())?;
+    let Some((id, gamepad)) = gilrs.gamepads().next() else {
+        return Err("No controller connected".to_string());
+    };
+    log::info!("Calibrating {}, leave its sticks at rest", gamepad.name());
+
+    let mut left = StickSamples::default();
+    let mut right = StickSamples::default();
+    let start = Instant::now();
+
+    while start.elapsed() < duration {
+        // Reading the events updates the axis values of the gamepad
+        while gilrs.next_event().is_some() {}
+
+        let gamepad = gilrs.gamepad(id);
+        left.add(gamepad.value(gilrs::Axis::LeftStickX), gamepad.value(gilrs::Axis::LeftStickY));
+        right.add(gamepad.value(gilrs::Axis::RightStickX), gamepad.value(gilrs::Axis::RightStickY));
+        std::thread::sleep(SAMPLE_INTERVAL);
+    }
+
+    Ok(format!(
+        "# Measured on {} over {}s\n{}",
+        gilrs.gamepad(id).name(),
+        duration.as_secs(),
+        suggested_config(&left, &right)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stick_samples() {
+        let mut samples = StickSamples::default();
+        assert_eq!(samples.center(), (0., 0.));
+
+        for (x, y) in [(0.02, -0.01), (0.04, -0.01), (0.03, -0.04), (0.03, 0.02)] {
+            samples.add(x, y);
+        }
+        let (x, y) = samples.center();
+        assert!((x - 0.03).abs() < 1e-6 && (y + 0.01).abs() < 1e-6);
+        // The farthest sample is 0.03 from the center
+        assert_eq!(samples.dead_zone(), 0.05);
+
+        assert_eq!(
+            suggested_config(&samples, &StickSamples::default()),
+            "left_stick_center_x = 0.030\nleft_stick_center_y = -0.010\nleft_stick_dead_zone = 0.05\n\
+             right_stick_center_x = 0.000\nright_stick_center_y = 0.000\nright_stick_dead_zone = 0.02\n"
+        );
+    }
+}
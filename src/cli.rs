@@ -0,0 +1,109 @@
+/// What the application was asked to do on the command line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Remap controller inputs (the default).
+    Run,
+    /// Print every input name that can be remapped.
+    ListInputs,
+    /// Print every keyboard key name accepted in remaps.
+    ListKeys,
+    /// Measure the sticks at rest and print the options correcting their drift.
+    Calibrate,
+    /// Write a commented config file listing every option, overwriting an existing one only if forced.
+    GenerateConfig { is_forced: bool },
+    /// Remap the controller events read from a replay file, then exit.
+    Replay(String),
+    /// Remap controller inputs as a background service, logging for the service manager.
+    Service,
+}
+
+/// The parsed command line.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Args {
+    pub action: Action,
+    /// Whether to log the input remaps would produce instead of injecting it.
+    pub is_dry_run: bool,
+}
+
+/// Parses the command-line arguments, excluding the program name.
+///
+/// # Arguments
+///
+/// * `args` - The arguments passed to the executable.
+///
+/// # Returns
+///
+/// The requested `Args`, or an error message for an unknown argument.
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut action = None;
+    let mut is_dry_run = false;
+    let mut is_forced = false;
+
+    while let Some(arg) = args.next() {
+        let arg_action = match arg.as_str() {
+            "--dry-run" => {
+                is_dry_run = true;
+                continue;
+            }
+            "--force" => {
+                is_forced = true;
+                continue;
+            }
+            "--list-inputs" => Action::ListInputs,
+            "--list-keys" => Action::ListKeys,
+            "--calibrate" => Action::Calibrate,
+            "--generate-config" => Action::GenerateConfig { is_forced: false },
+            "--service" => Action::Service,
+            "--replay" => match args.next() {
+                Some(path) => Action::Replay(path),
+                None => return Err("Missing replay file after --replay".to_string()),
+            },
+            _ if action.is_some() => return Err(format!("Unexpected argument: {arg}")),
+            _ => return Err(format!("Unknown argument: {arg}")),
+        };
+
+        if action.replace(arg_action).is_some() {
+            return Err(format!("Unexpected argument: {arg}"));
+        }
+    }
+
+    let action = match action.unwrap_or(Action::Run) {
+        Action::GenerateConfig { .. } => Action::GenerateConfig { is_forced },
+        _ if is_forced => return Err("--force only applies to --generate-config".to_string()),
+        action => action,
+    };
+    Ok(Args { action, is_dry_run })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&[]).unwrap().action, Action::Run);
+        assert_eq!(
+            parse(&["--replay", "events.jsonl", "--dry-run"]).unwrap(),
+            Args {
+                action: Action::Replay("events.jsonl".to_string()),
+                is_dry_run: true
+            }
+        );
+        assert!(parse(&["--dry-run"]).unwrap().is_dry_run);
+        assert_eq!(parse(&["--calibrate"]).unwrap().action, Action::Calibrate);
+        assert_eq!(
+            parse(&["--force", "--generate-config"]).unwrap().action,
+            Action::GenerateConfig { is_forced: true }
+        );
+        assert_eq!(parse(&["--generate-config"]).unwrap().action, Action::GenerateConfig { is_forced: false });
+
+        assert_eq!(parse(&["--jump"]).unwrap_err(), "Unknown argument: --jump");
+        assert_eq!(parse(&["--service", "--list-keys"]).unwrap_err(), "Unexpected argument: --list-keys");
+        assert_eq!(parse(&["--replay"]).unwrap_err(), "Missing replay file after --replay");
+        assert_eq!(parse(&["--force"]).unwrap_err(), "--force only applies to --generate-config");
+    }
+}
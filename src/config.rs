@@ -1,7 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
-use duration_str::deserialize_duration;
+use duration_str::{deserialize_duration, deserialize_option_duration};
+
+use crate::event::StickAxis;
+use crate::names::{chord_inputs, field_names, is_input_name, is_remappable_name, variant_names};
+use crate::stick::Stick;
+
+/// Version of the config layout, raised whenever options are renamed or reshaped.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// A config file listing every option commented out, with its default value, written by `--generate-config`.
+pub const CONFIG_TEMPLATE: &str = include_str!("config_template.toml");
 
 /// Represents different types of input remappings.
 #[derive(Debug, serde::Deserialize)]
@@ -11,18 +21,582 @@ pub enum Remap {
     Seq(Vec<enigo::Key>),
     /// A set of keys to be pressed and released simultaneously.
     Sync(Vec<enigo::Key>),
+    /// A set of keys latched down by a press and released by the next one.
+    Toggle(Vec<enigo::Key>),
+    /// A key pressed when the input is pressed, and left down until a `KeyUp` releases it.
+    KeyDown(enigo::Key),
+    /// A key released when the input is pressed, e.g. after a `KeyDown` bound to another input.
+    KeyUp(enigo::Key),
     /// A key to be repeated while the input is active.
-    Repeat(enigo::Key),
+    Repeat(RepeatKey),
+    /// Key presses, releases and delays played in order when the input is pressed.
+    Macro(Vec<MacroStep>),
     /// A mouse button action.
     Mouse(enigo::Button),
+    /// A mouse button clicked once when the input is pressed.
+    MouseClick(enigo::Button),
+    /// A fixed relative mouse movement, in pixels.
+    #[serde(alias = "mouse_move")]
+    Nudge {
+        dx: i32,
+        dy: i32,
+        /// Whether to repeat the movement while the input is held, with the key repeat delays.
+        #[serde(default)]
+        repeat: bool,
+    },
+    /// A move of the mouse cursor to a fixed position on the screen, in pixels.
+    MoveTo { x: i32, y: i32 },
+    /// A mouse wheel scroll, in wheel ticks.
+    Scroll {
+        /// Horizontal ticks, positive to the right.
+        #[serde(default)]
+        x: i32,
+        /// Vertical ticks, positive downwards.
+        #[serde(default)]
+        y: i32,
+    },
+    /// A mouse wheel scroll repeated while the input is held.
+    ScrollRepeat {
+        axis: enigo::Axis,
+        /// Ticks per scroll, positive downwards or to the right.
+        amount: i32,
+        /// Delay between two scrolls.
+        #[serde(deserialize_with = "deserialize_duration")]
+        interval: Duration,
+    },
     /// A command-line instruction to execute.
-    Command(String),
+    Command(CommandLine),
+    /// A text typed one character at a time, independently of the keyboard layout.
+    TypeChars(String),
+    /// A text typed at once when the input is pressed.
+    Text(String),
+    /// A toggle making the alternative remap set the default one, and back.
+    SwapLayers,
+    /// A switch to another config file, relative to the directory of the default one.
+    SwitchProfile(String),
+    /// No action, masking the binding the input would otherwise inherit, e.g. from `main` in `alt`.
+    Block,
+    /// A remap chosen by whether the input is pressed once or twice within `double_tap_window`.
+    DoubleTap {
+        single: Box<Remap>,
+        double: Box<Remap>,
+    },
+    /// A remap chosen by whether the input is released before `hold_duration`, or held past it.
+    HoldTap {
+        tap: Box<Remap>,
+        hold: Box<Remap>,
+        #[serde(deserialize_with = "deserialize_duration")]
+        hold_duration: Duration,
+    },
+    /// A remap fired only once the input is held for `delay`, e.g. to guard a quit binding.
+    Delayed {
+        inner: Box<Remap>,
+        #[serde(deserialize_with = "deserialize_duration")]
+        delay: Duration,
+    },
+    /// A remap pressed and released repeatedly while the input is held.
+    Turbo {
+        inner: Box<Remap>,
+        /// Delay between two presses.
+        #[serde(deserialize_with = "deserialize_duration")]
+        interval: Duration,
+    },
+    /// A turbo whose speed follows how far the analog trigger bound to it is pulled.
+    Analog {
+        inner: Box<Remap>,
+        /// Presses per second at the activation threshold.
+        min_rate: f32,
+        /// Presses per second at full pull.
+        max_rate: f32,
+        /// Exponent applied to the pull, `1` for a linear response.
+        #[serde(default = "Remap::default_analog_curve")]
+        curve: f32,
+    },
+}
+
+impl Remap {
+    /// Validates the remap, including the ones nested in it.
+    pub fn check_error(&self) -> Result<(), String> {
+        match self {
+            Remap::Seq(keys) | Remap::Sync(keys) | Remap::Toggle(keys) if keys.is_empty() => {
+                Err("Empty key list".to_string())
+            }
+            Remap::Macro(steps) if steps.is_empty() => Err("Empty macro".to_string()),
+            Remap::Repeat(repeat) if !(repeat.acceleration > 0.0 && repeat.acceleration <= 1.0) => {
+                Err("Repeat acceleration out of 0..1".to_string())
+            }
+            Remap::Repeat(repeat) if repeat.acceleration < 1.0 && repeat.sub_delay_min.is_none() => {
+                Err("Accelerating repeat without sub_delay_min".to_string())
+            }
+            Remap::Text(text) if text.is_empty() => Err("Empty text".to_string()),
+            Remap::SwitchProfile(path) if path.is_empty() => Err("Empty profile path".to_string()),
+            Remap::Command(command) if command.cmdlines().next().is_none() => {
+                Err("Empty command".to_string())
+            }
+            Remap::Command(command) => match command
+                .cmdlines()
+                .find(|cmdline| shlex::split(cmdline).is_none())
+            {
+                Some(cmdline) => Err(format!("Unparseable command: {cmdline}")),
+                None => Ok(()),
+            },
+            Remap::DoubleTap { single, double } => {
+                single.check_error()?;
+                double.check_error()
+            }
+            Remap::HoldTap { tap, hold, .. } => {
+                tap.check_error()?;
+                hold.check_error()
+            }
+            Remap::Delayed { inner, .. } => inner.check_error(),
+            Remap::Turbo { interval, .. } if interval.is_zero() => Err("Zero turbo interval".to_string()),
+            Remap::Analog { min_rate, max_rate, .. } if *min_rate <= 0.0 || *max_rate <= 0.0 => {
+                Err("Non-positive analog rate".to_string())
+            }
+            Remap::Analog { curve, .. } if *curve <= 0.0 => Err("Non-positive analog curve".to_string()),
+            Remap::Turbo { inner, .. } | Remap::Analog { inner, .. } if inner.contains_turbo() => {
+                Err("Nested turbo".to_string())
+            }
+            Remap::Turbo { inner, .. } | Remap::Analog { inner, .. } => inner.check_error(),
+            _ => Ok(()),
+        }
+    }
+
+    fn default_analog_curve() -> f32 {
+        1.0
+    }
+
+    /// Returns the remap and the ones nested in it, at any depth.
+    fn remaps(&self) -> Vec<&Remap> {
+        let nested_remaps = match self {
+            Remap::DoubleTap { single, double } => vec![single, double],
+            Remap::HoldTap { tap, hold, .. } => vec![tap, hold],
+            Remap::Delayed { inner, .. } | Remap::Turbo { inner, .. } | Remap::Analog { inner, .. } => vec![inner],
+            _ => Vec::new(),
+        };
+        std::iter::once(self)
+            .chain(nested_remaps.into_iter().flat_map(|remap| remap.remaps()))
+            .collect()
+    }
+
+    /// Checks whether the remap is a turbo, analog or not, or nests one.
+    fn contains_turbo(&self) -> bool {
+        match self {
+            Remap::Turbo { .. } | Remap::Analog { .. } => true,
+            Remap::DoubleTap { single, double } => single.contains_turbo() || double.contains_turbo(),
+            Remap::HoldTap { tap, hold, .. } => tap.contains_turbo() || hold.contains_turbo(),
+            Remap::Delayed { inner, .. } => inner.contains_turbo(),
+            _ => false,
+        }
+    }
+}
+
+/// A step of a `macro` remap.
+#[derive(Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroStep {
+    Press(enigo::Key),
+    Release(enigo::Key),
+    Delay(#[serde(deserialize_with = "deserialize_duration")] Duration),
+}
+
+/// A key repeated while its input is held, with delays overriding the global ones.
+#[derive(Debug, serde::Deserialize)]
+#[serde(from = "RepeatKeyForm")]
+pub struct RepeatKey {
+    pub key: enigo::Key,
+    /// Delay before the repeats start, instead of `key_repeat_initial_delay`.
+    pub initial_delay: Option<Duration>,
+    /// Delay between the repeats, instead of `key_repeat_sub_delay`.
+    pub sub_delay: Option<Duration>,
+    /// Shortest delay between the repeats once `acceleration` has shrunk it.
+    pub sub_delay_min: Option<Duration>,
+    /// Factor applied to the delay between the repeats after each one, `1` for a steady repeat.
+    pub acceleration: f32,
+}
+
+impl RepeatKey {
+    /// Returns the delay following the given number of repeats, shrunk by the acceleration down to `sub_delay_min`.
+    pub fn delay_after(&self, repeats: u32, default_sub_delay: Duration) -> Duration {
+        let sub_delay = self.sub_delay.unwrap_or(default_sub_delay);
+        match self.sub_delay_min {
+            Some(sub_delay_min) => {
+                sub_delay.mul_f64(f64::from(self.acceleration).powf(repeats.into())).max(sub_delay_min)
+            }
+            None => sub_delay,
+        }
+    }
+
+    fn default_acceleration() -> f32 {
+        1.0
+    }
+}
+
+/// The ways a `RepeatKey` can be written: a bare key, or a table with its delays.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum RepeatKeyForm {
+    Key(enigo::Key),
+    Timed {
+        key: enigo::Key,
+        #[serde(default, deserialize_with = "deserialize_option_duration")]
+        initial_delay: Option<Duration>,
+        #[serde(default, deserialize_with = "deserialize_option_duration")]
+        sub_delay: Option<Duration>,
+        #[serde(default, deserialize_with = "deserialize_option_duration")]
+        sub_delay_min: Option<Duration>,
+        #[serde(default = "RepeatKey::default_acceleration")]
+        acceleration: f32,
+    },
+}
+
+impl From<RepeatKeyForm> for RepeatKey {
+    fn from(form: RepeatKeyForm) -> Self {
+        match form {
+            RepeatKeyForm::Key(key) => RepeatKey {
+                key,
+                initial_delay: None,
+                sub_delay: None,
+                sub_delay_min: None,
+                acceleration: RepeatKey::default_acceleration(),
+            },
+            RepeatKeyForm::Timed {
+                key,
+                initial_delay,
+                sub_delay,
+                sub_delay_min,
+                acceleration,
+            } => RepeatKey {
+                key,
+                initial_delay,
+                sub_delay,
+                sub_delay_min,
+                acceleration,
+            },
+        }
+    }
+}
+
+/// Commands run by a remap when its input is pressed and released, with the environment they run in.
+#[derive(Debug, serde::Deserialize)]
+#[serde(from = "CommandLineForm")]
+pub struct CommandLine {
+    /// The program and its arguments run on press, split like a shell would.
+    pub on_press: Option<String>,
+    /// The program and its arguments run on release.
+    pub on_release: Option<String>,
+    /// Directory the commands run in, instead of the current one.
+    pub cwd: Option<String>,
+    /// Variables added to the environment of the commands.
+    pub env: HashMap<String, String>,
+}
+
+impl CommandLine {
+    /// Returns the command line to run when the input is pressed or released, unless it is empty.
+    pub fn cmdline(&self, is_press_down: bool) -> Option<&str> {
+        let cmdline = if is_press_down {
+            &self.on_press
+        } else {
+            &self.on_release
+        };
+        cmdline
+            .as_deref()
+            .filter(|cmdline| !cmdline.trim().is_empty())
+    }
+
+    /// Returns the command lines that are not empty.
+    fn cmdlines(&self) -> impl Iterator<Item = &str> {
+        [self.cmdline(true), self.cmdline(false)].into_iter().flatten()
+    }
+}
+
+/// The ways a `CommandLine` can be written: a bare command line run on press, or a table.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum CommandLineForm {
+    Cmdline(String),
+    Full {
+        #[serde(alias = "cmdline")]
+        on_press: Option<String>,
+        on_release: Option<String>,
+        cwd: Option<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+    },
+}
+
+impl From<CommandLineForm> for CommandLine {
+    fn from(form: CommandLineForm) -> Self {
+        match form {
+            CommandLineForm::Cmdline(cmdline) => CommandLine {
+                on_press: Some(cmdline),
+                on_release: None,
+                cwd: None,
+                env: HashMap::new(),
+            },
+            CommandLineForm::Full {
+                on_press,
+                on_release,
+                cwd,
+                env,
+            } => CommandLine {
+                on_press,
+                on_release,
+                cwd,
+                env,
+            },
+        }
+    }
+}
+
+/// A remap bound to a controller input, with the conditions under which it fires.
+#[derive(Debug, serde::Deserialize)]
+pub struct Binding {
+    /// The action performed by the binding.
+    #[serde(flatten)]
+    pub remap: Remap,
+    /// A controller input that suppresses the binding while held.
+    pub unless: Option<String>,
+    /// The window that must be focused for the binding to fire.
+    pub when_window: Option<WindowMatcher>,
+    /// Feedback played when the binding fires.
+    pub feedback: Option<Feedback>,
+}
+
+/// Confirmation played when a binding fires.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(default)]
+pub struct Feedback {
+    /// Duration of the controller rumble in milliseconds, or `0` for none.
+    pub rumble_ms: u32,
+    /// Intensity of the controller rumble, between `0` and `1`.
+    pub rumble_strength: f32,
+    /// Whether to play the system beep.
+    pub beep: bool,
+}
+
+impl Default for Feedback {
+    fn default() -> Self {
+        Self {
+            rumble_ms: 0,
+            rumble_strength: 1.0,
+            beep: false,
+        }
+    }
+}
+
+impl Feedback {
+    /// Validates the feedback.
+    fn check_error(&self) -> Result<(), String> {
+        if (0.0..=1.0).contains(&self.rumble_strength) {
+            Ok(())
+        } else {
+            Err("Rumble strength out of 0..1".to_string())
+        }
+    }
+}
+
+impl Binding {
+    /// Checks whether the `unless` condition of the binding is met.
+    ///
+    /// # Arguments
+    ///
+    /// * `held_inputs` - The names of the controller inputs currently held down.
+    pub fn is_suppressed(&self, held_inputs: &HashSet<String>) -> bool {
+        self.unless
+            .as_ref()
+            .is_some_and(|unless| held_inputs.contains(unless))
+    }
+}
+
+/// A condition on the focused window, met when all of its given parts match.
+#[derive(Debug, serde::Deserialize)]
+pub struct WindowMatcher {
+    /// File name of the executable owning the window, e.g. `game.exe`, regardless of case.
+    pub process: Option<String>,
+    /// Text contained in the window title, regardless of case.
+    pub title: Option<String>,
+}
+
+impl WindowMatcher {
+    /// Checks whether a window, given by its executable file name and title, matches.
+    pub fn matches(&self, process: &str, title: &str) -> bool {
+        self.process
+            .as_ref()
+            .is_none_or(|expected| expected.eq_ignore_ascii_case(process))
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|expected| title.to_lowercase().contains(&expected.to_lowercase()))
+    }
+}
+
+/// Which neighbor a right stick push lying exactly on a diagonal resolves to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagonalBias {
+    /// Diagonals resolve to up or down.
+    #[default]
+    Vertical,
+    /// Diagonals resolve to left or right.
+    Horizontal,
+}
+
+/// Remap sets and stick settings used instead of the top-level ones for a specific controller.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct GamepadProfile {
+    /// Main remap configuration of the controller.
+    pub main: HashMap<String, Binding>,
+    /// Alternative remap configuration of the controller.
+    pub alt: HashMap<String, Binding>,
+    /// What the left stick of the controller is used for, over `mouse_stick`.
+    pub left_stick_mode: Option<StickMode>,
+    /// What the right stick of the controller is used for, over `mouse_stick`.
+    pub right_stick_mode: Option<StickMode>,
+    /// Dead zone threshold for the left stick of the controller.
+    pub left_stick_dead_zone: Option<f32>,
+    /// Dead zone threshold for the right stick of the controller.
+    pub right_stick_dead_zone: Option<f32>,
+    /// Trigger zone threshold for the right stick of the controller.
+    pub right_stick_trigger_zone: Option<f32>,
+    /// Initial speed for mouse movement by the controller.
+    pub mouse_initial_speed: Option<f32>,
+    /// Maximum speed for mouse movement by the controller.
+    pub mouse_max_speed: Option<f32>,
+}
+
+/// Stick settings of a controller, its own ones merged over the top-level ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StickSettings {
+    /// What the left stick is used for.
+    pub left_stick_mode: StickMode,
+    /// What the right stick is used for.
+    pub right_stick_mode: StickMode,
+    /// Dead zone threshold for the left stick.
+    pub left_stick_dead_zone: f32,
+    /// Dead zone threshold for the right stick.
+    pub right_stick_dead_zone: f32,
+    /// Trigger zone threshold for the right stick.
+    pub right_stick_trigger_zone: f32,
+    /// Initial speed for mouse movement.
+    pub mouse_initial_speed: f32,
+    /// Maximum speed for mouse movement.
+    pub mouse_max_speed: f32,
 }
 
+impl StickSettings {
+    /// Returns what a stick is used for.
+    pub fn stick_mode(&self, stick: Stick) -> StickMode {
+        match stick {
+            Stick::Left => self.left_stick_mode,
+            Stick::Right => self.right_stick_mode,
+        }
+    }
+}
+
+/// How the mouse speed ramps up from `mouse_initial_speed` to `mouse_max_speed`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccelerationCurve {
+    /// The speed grows by the same amount every tick.
+    #[default]
+    Linear,
+    /// The speed grows slowly at first, then faster.
+    Quadratic,
+    /// The speed is multiplied by the same factor every tick.
+    Exponential,
+}
+
+/// How the dead zone of a stick moving the mouse is applied.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadZoneShape {
+    /// The stick vector is shortened by the dead zone size.
+    #[default]
+    Radial,
+    /// Each axis is shortened by the dead zone size independently.
+    Axial,
+    /// Like `Radial`, then stretched so a full push still reaches full length.
+    Scaled,
+}
+
+/// How mouse movements are rounded to whole pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseRounding {
+    /// Fractions of pixels are carried over to the next movements.
+    #[default]
+    Accumulate,
+    /// Fractions of pixels are dropped.
+    Truncate,
+    /// Movements are rounded to the nearest pixel, halves away from zero.
+    Nearest,
+    /// Movements are rounded to the nearest pixel, halves to the even one.
+    HalfEven,
+}
+
+/// What a stick is used for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StickMode {
+    /// The stick moves the mouse cursor, with acceleration.
+    #[default]
+    Mouse,
+    /// The stick fires one of four directional inputs when pushed.
+    Directional,
+    /// The stick position is the cursor position on the main display, its center the display center.
+    Absolute,
+    /// The stick scrolls the mouse wheel, faster the farther it is pushed.
+    Scroll,
+}
+
+/// How an activator selects its remap set.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivatorMode {
+    /// The set is used while the activator is held.
+    #[default]
+    Hold,
+    /// A press latches the set until the next press.
+    Toggle,
+}
+
+/// How two opposite directions held at the same time are resolved, e.g. `dpad_left` and `dpad_right`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SocdResolution {
+    /// Both directions fire.
+    #[default]
+    None,
+    /// The direction pressed last overrides the other one.
+    LastWins,
+    /// Neither direction fires until one of them is released.
+    Neutral,
+    /// The direction pressed first keeps firing.
+    FirstWins,
+}
+
+/// How a change of the active remap set is shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerIndicator {
+    /// Changes are not shown.
+    #[default]
+    None,
+    /// Changes are logged.
+    Log,
+    /// Changes are logged and shown in a desktop notification.
+    Notification,
+}
 /// Configuration settings for input remapping and behavior.
 #[derive(Debug, Default, serde::Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// Version of the layout the file is written for, the current one if not given.
+    #[serde(default = "Config::default_version")]
+    pub version: u32,
+
     /// Initial delay before key repeat starts.
     #[serde(
         deserialize_with = "deserialize_duration",
@@ -35,6 +609,42 @@ pub struct Config {
         default = "Config::default_key_repeat_sub_delay"
     )]
     pub key_repeat_sub_delay: Duration,
+    /// Delay between characters typed by a `type_chars` remap.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub type_chars_delay: Duration,
+
+    /// Time within which a second press makes a `double_tap` remap fire its `double` action.
+    #[serde(
+        deserialize_with = "deserialize_duration",
+        default = "Config::default_double_tap_window"
+    )]
+    pub double_tap_window: Duration,
+    /// Whether pressing a `macro` remap while a macro is playing stops it and starts over.
+    pub macro_cancel_on_press: bool,
+
+    /// Largest absolute value accepted from a stick axis; the driver's values are clamped to it.
+    #[serde(default = "Config::default_stick_value_limit")]
+    pub stick_value_limit: f32,
+
+    /// Values the stick axes report at rest, subtracted from their values.
+    pub left_stick_center_x: f32,
+    pub left_stick_center_y: f32,
+    pub right_stick_center_x: f32,
+    pub right_stick_center_y: f32,
+    /// Whether the values of the stick axes are negated.
+    pub left_stick_invert_x: bool,
+    pub left_stick_invert_y: bool,
+    pub right_stick_invert_x: bool,
+    pub right_stick_invert_y: bool,
+
+    /// What the left stick is used for.
+    #[serde(default = "Config::default_left_stick_mode")]
+    pub left_stick_mode: StickMode,
+    /// What the right stick is used for.
+    #[serde(default = "Config::default_right_stick_mode")]
+    pub right_stick_mode: StickMode,
+    /// The stick moving the mouse, the other one firing directions, overriding both stick modes.
+    pub mouse_stick: Option<Stick>,
 
     /// Polling interval for the left stick.
     #[serde(
@@ -42,9 +652,22 @@ pub struct Config {
         default = "Config::default_left_stick_poll_interval"
     )]
     pub left_stick_poll_interval: Duration,
+    /// Shortest polling interval for the left stick, reached while it keeps moving.
+    #[serde(deserialize_with = "deserialize_option_duration")]
+    pub left_stick_poll_interval_min: Option<Duration>,
     /// Dead zone threshold for the left stick.
     #[serde(default = "Config::default_left_stick_dead_zone")]
     pub left_stick_dead_zone: f32,
+    /// Shape of the dead zone of the left stick.
+    pub left_stick_dead_zone_shape: DeadZoneShape,
+    /// Whether the movement of the left stick past its dead zone starts from zero, rather than from the push.
+    #[serde(default = "Config::default_left_stick_dead_zone_rescale")]
+    pub left_stick_dead_zone_rescale: bool,
+    /// Exponent of the curve applied to how far the left stick is pushed, when moving the mouse.
+    #[serde(default = "Config::default_left_stick_response_curve")]
+    pub left_stick_response_curve: f32,
+    /// Whether the left stick moves the mouse as fast on the diagonals as straight.
+    pub left_stick_normalize_diagonal: bool,
 
     /// Initial speed for mouse movement.
     #[serde(default = "Config::default_mouse_initial_speed")]
@@ -55,6 +678,39 @@ pub struct Config {
     /// Number of ticks to reach maximum mouse speed.
     #[serde(default = "Config::default_mouse_ticks_to_reach_max_speed")]
     pub mouse_ticks_to_reach_max_speed: f32,
+    /// Shape of the ramp from the initial to the maximum mouse and scroll speeds.
+    pub mouse_acceleration_curve: AccelerationCurve,
+    /// Initial multiplier of the `scroll_repeat` amounts.
+    #[serde(default = "Config::default_scroll_speed")]
+    pub scroll_initial_speed: f32,
+    /// Maximum multiplier of the `scroll_repeat` amounts.
+    #[serde(default = "Config::default_scroll_speed")]
+    pub scroll_max_speed: f32,
+    /// Number of scrolls to reach the maximum scroll speed.
+    #[serde(default = "Config::default_scroll_ticks_to_reach_max_speed")]
+    pub scroll_ticks_to_reach_max_speed: f32,
+    /// Multiplier of the horizontal mouse movement.
+    #[serde(default = "Config::default_mouse_sensitivity")]
+    pub mouse_sensitivity_x: f32,
+    /// Multiplier of the vertical mouse movement.
+    #[serde(default = "Config::default_mouse_sensitivity")]
+    pub mouse_sensitivity_y: f32,
+    /// Whether pushing a stick up moves the mouse up, the stick and screen Y axes pointing opposite ways.
+    #[serde(default = "Config::default_mouse_invert_y")]
+    pub mouse_invert_y: bool,
+    /// How mouse movements are rounded to whole pixels.
+    pub mouse_rounding: MouseRounding,
+    /// Whether motion events of the controller move the mouse, on top of the stick.
+    pub gyro_enabled: bool,
+    /// Mouse movement in pixels per degree the controller turns.
+    #[serde(default = "Config::default_gyro_sensitivity")]
+    pub gyro_sensitivity: f32,
+    /// Time during which mouse movement is suppressed after a right stick direction fires.
+    #[serde(deserialize_with = "deserialize_option_duration")]
+    pub mouse_freeze_after_direction: Option<Duration>,
+    /// Minimum time between two mouse movements by a stick, the pixels of the polls in between being added up.
+    #[serde(deserialize_with = "deserialize_option_duration")]
+    pub mouse_move_flush_interval: Option<Duration>,
 
     /// Polling interval for the right stick.
     #[serde(
@@ -68,59 +724,570 @@ pub struct Config {
     /// Dead zone threshold for the right stick.
     #[serde(default = "Config::default_right_stick_dead_zone")]
     pub right_stick_dead_zone: f32,
+    /// Wheel ticks scrolled per poll by a stick in scroll mode pushed all the way.
+    #[serde(default = "Config::default_stick_scroll_speed")]
+    pub stick_scroll_speed: f32,
+    /// Number of directions a stick in directional mode fires, 4 or 8.
+    #[serde(default = "Config::default_stick_directions")]
+    pub stick_directions: u8,
+    /// Direction chosen when the right stick is exactly on a diagonal.
+    pub right_stick_diagonal_bias: DiagonalBias,
+    /// Whether a flick in and out of the trigger zone between two polls still fires.
+    pub right_stick_flick_detection: bool,
+    /// How opposite directions held at the same time are resolved.
+    pub socd_resolution: SocdResolution,
+
+    /// Pull of the analog triggers at which they are pressed, instead of their digital events.
+    pub trigger_activation_threshold: Option<f32>,
+    /// How far below its activation threshold a pressed trigger or stick direction must be let go to be released.
+    pub hysteresis: Option<f32>,
+
+    /// Poll interval of both sticks while the device runs on battery.
+    #[serde(deserialize_with = "deserialize_option_duration")]
+    pub battery_poll_interval: Option<Duration>,
+    /// Maximum mouse speed while the device runs on battery.
+    pub battery_mouse_speed: Option<f32>,
+
+    /// Number of threads running the remapping tasks, read at startup.
+    pub worker_threads: Option<usize>,
+    /// Whether the keys held by the output backend are released when dogmu exits, read at startup.
+    #[serde(default = "Config::default_enigo_release_keys_on_drop")]
+    pub enigo_release_keys_on_drop: bool,
+    /// Milliseconds the output backend waits after each input on Linux X11, read at startup.
+    pub enigo_linux_delay: Option<u32>,
+    /// Milliseconds the output backend waits after each input on macOS, read at startup.
+    pub enigo_mac_delay: Option<u32>,
+
+    /// Time a controller may stay disconnected before its state is reset.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub disconnect_debounce: Duration,
+    /// Time within which a release followed by a press of the same button are both ignored.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub button_debounce: Duration,
+    /// Whether held buttons are released when the focused window changes or the desktop is locked.
+    pub release_on_focus_change: bool,
+    /// Whether the guide button fires the `mode` bindings, rather than being left to the system.
+    #[serde(default = "Config::default_intercept_mode_button")]
+    pub intercept_mode_button: bool,
 
     /// Optional activator for the alternative remap set.
     pub alternative_activator: Option<String>,
+    /// Whether the alternative activator is held or toggled.
+    pub alternative_activator_mode: ActivatorMode,
+    /// Whether the alternative remap set falls back to the main one for the inputs it does not remap.
+    pub alt_inherits_main: bool,
+    /// Inputs selecting the alternative remap set while they are all held.
+    pub chord_activator: Vec<String>,
+    /// Feedback played when the activator is pressed.
+    pub activator_feedback: Option<Feedback>,
+    /// How a change of the active remap set is shown.
+    pub layer_indicator: LayerIndicator,
+    /// Names usable in place of input names, e.g. `cross = 'south'`.
+    pub button_aliases: HashMap<String, String>,
 
     /// Main remap configuration.
-    pub main: HashMap<String, Remap>,
+    pub main: HashMap<String, Binding>,
     /// Alternative remap configuration.
-    pub alt: HashMap<String, Remap>,
+    pub alt: HashMap<String, Binding>,
+    /// Remap sets and stick settings of specific controllers, keyed by the name they report.
+    pub gamepads: HashMap<String, GamepadProfile>,
+
+    /// Activators of the named layers, keyed by layer name.
+    pub layer_activators: HashMap<String, String>,
+    /// Whether the activators of the named layers are held or toggled, keyed by layer name.
+    pub layer_activator_modes: HashMap<String, ActivatorMode>,
+    /// Named remap layers, used over `main` and `alt` while their activator is held.
+    pub layers: HashMap<String, HashMap<String, Binding>>,
+
+    /// The chords remapped in any set, gathered once aliases are resolved since every press looks them up.
+    #[serde(skip)]
+    chords: Vec<String>,
 }
 
 impl Config {
-    /// Validates the configuration and returns an error if invalid.
-    pub fn check_error(self) -> Result<Self, &'static str> {
-        if self.left_stick_dead_zone <= 0.0
-            || self.right_stick_trigger_zone <= 0.0
-            || self.right_stick_dead_zone <= 0.0
-        {
-            return Err("Negative zone size");
+    /// Validates the configuration.
+    ///
+    /// # Returns
+    ///
+    /// The configuration, or a description of every problem found in it.
+    pub fn check_error(self) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.version > CONFIG_VERSION {
+            errors.push(format!(
+                "Config version {} is newer than the supported {CONFIG_VERSION}, update dogmu",
+                self.version
+            ));
+        }
+
+        let global_stick_errors = self.stick_errors(&self.stick_settings(None));
+        errors.extend(global_stick_errors.iter().cloned());
+
+        let mut gamepads = self.gamepads.keys().collect::<Vec<_>>();
+        gamepads.sort();
+        for gamepad in gamepads {
+            // Only the errors the controller's own settings cause are reported for it
+            for err in self.stick_errors(&self.stick_settings(Some(gamepad))) {
+                if !global_stick_errors.contains(&err) {
+                    errors.push(format!("{err} in gamepads.'{gamepad}'"));
+                }
+            }
+        }
+
+        let positive_options = [
+            ("left_stick_response_curve", self.left_stick_response_curve),
+            ("stick_value_limit", self.stick_value_limit),
+        ];
+        for (option, value) in positive_options {
+            if value <= 0.0 {
+                errors.push(format!("{option} ({value}) must be positive"));
+            }
+        }
+
+        if self.mouse_acceleration_curve == AccelerationCurve::Exponential && self.scroll_initial_speed <= 0.0 {
+            errors.push(format!(
+                "scroll_initial_speed ({}) must be positive with an exponential mouse_acceleration_curve",
+                self.scroll_initial_speed
+            ));
+        }
+
+        let unit_options = [
+            ("trigger_activation_threshold", self.trigger_activation_threshold),
+            ("hysteresis", self.hysteresis),
+        ];
+        for (option, value) in unit_options {
+            if let Some(value) = value
+                && !(0.0..=1.0).contains(&value)
+            {
+                errors.push(format!("{option} ({value}) must be between 0 and 1"));
+            }
         }
 
-        if self.right_stick_trigger_zone < self.right_stick_dead_zone {
-            return Err("Trigger zone smaller than dead zone");
+        if self.worker_threads == Some(0) {
+            errors.push("worker_threads (0) must be positive".to_string());
+        }
+
+        if self.stick_directions != 4 && self.stick_directions != 8 {
+            errors.push(format!("stick_directions ({}) must be 4 or 8", self.stick_directions));
         }
 
         if let Some(activator) = &self.alternative_activator {
-            if self.main.contains_key(activator) {
-                return Err("Activator for alternative set is remapped");
+            if !is_input_name(activator) {
+                errors.push(format!("alternative_activator ({activator}) is an unknown input"));
+            }
+            if self.main.contains_key(activator)
+                || self.gamepads.values().any(|profile| profile.main.contains_key(activator))
+            {
+                errors.push(format!("alternative_activator ({activator}) is remapped in a main set"));
+            }
+        }
+
+        if self.chord_activator.len() == 1 {
+            errors.push(format!("chord_activator ({}) must have at least 2 inputs", self.chord_activator[0]));
+        }
+        for input in &self.chord_activator {
+            if !is_input_name(input) {
+                errors.push(format!("chord_activator ({input}) is an unknown input"));
+            }
+            if Some(input) == self.alternative_activator.as_ref() {
+                errors.push(format!("alternative_activator ({input}) cannot be in chord_activator"));
+            }
+        }
+
+        let mut layer_activators = self.layer_activators.iter().collect::<Vec<_>>();
+        layer_activators.sort();
+
+        for (index, (layer, activator)) in layer_activators.iter().enumerate() {
+            if !self.layers.contains_key(*layer) {
+                errors.push(format!("layer_activators.{layer} ({activator}) names no layer in layers"));
+            }
+            if !is_input_name(activator) {
+                errors.push(format!("layer_activators.{layer} ({activator}) is an unknown input"));
+            }
+            if self.main.contains_key(*activator)
+                || self.gamepads.values().any(|profile| profile.main.contains_key(*activator))
+            {
+                errors.push(format!("layer_activators.{layer} ({activator}) is remapped in a main set"));
+            }
+            if Some(*activator) == self.alternative_activator.as_ref() {
+                errors.push(format!("layer_activators.{layer} ({activator}) is also the alternative_activator"));
+            }
+            if self.chord_activator.contains(activator) {
+                errors.push(format!("layer_activators.{layer} ({activator}) cannot be in chord_activator"));
+            }
+            // Only the first of the layers sharing an activator would ever be activated
+            if let Some((other_layer, _)) = layer_activators[..index].iter().find(|(_, other)| other == activator) {
+                errors.push(format!("layer_activators.{layer} ({activator}) already activates layer {other_layer}"));
+            }
+        }
+
+        if let Some(Err(err)) = self.activator_feedback.as_ref().map(Feedback::check_error) {
+            errors.push(format!("{err} in activator_feedback"));
+        }
+
+        for (set_name, remaps) in self.remap_sets() {
+            let mut inputs = remaps.iter().collect::<Vec<_>>();
+            inputs.sort_by_key(|(input, _)| *input);
+
+            for (input, binding) in inputs {
+                if !is_remappable_name(input) {
+                    errors.push(format!("Unknown input in {set_name}: {input}"));
+                }
+                if let Some(unless) = &binding.unless
+                    && !is_input_name(unless)
+                {
+                    errors.push(format!("Unknown input in {set_name}.{input}.unless: {unless}"));
+                }
+                if let Err(err) = binding.remap.check_error() {
+                    errors.push(format!("{err} in {set_name}.{input}"));
+                }
+                if let Some(Err(err)) = binding.feedback.as_ref().map(Feedback::check_error) {
+                    errors.push(format!("{err} in {set_name}.{input}.feedback"));
+                }
+                if let Some(WindowMatcher { process: None, title: None }) = binding.when_window {
+                    errors.push(format!("Empty window condition in {set_name}.{input}.when_window"));
+                }
+                if input == "mode" && !self.intercept_mode_button {
+                    errors.push(format!("Mode button remapped without intercept_mode_button in {set_name}"));
+                }
+            }
+        }
+
+        // Not an error, since the key is still released by a reload or a disconnect
+        for key in self.unreleased_keys() {
+            log::warn!("{key:?} is pressed by a key_down but released by no key_up");
+        }
+
+        if errors.is_empty() { Ok(self) } else { Err(errors) }
+    }
+
+    /// Replaces the `button_aliases` written in place of input names by the input names.
+    ///
+    /// The chords of the resolved sets are gathered too, for `chord_names`.
+    ///
+    /// # Returns
+    ///
+    /// The resolved config, or every invalid alias.
+    pub fn resolve_aliases(mut self) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        let mut aliases = self.button_aliases.iter().collect::<Vec<_>>();
+        aliases.sort();
+        for (alias, input) in aliases {
+            if is_input_name(alias) {
+                errors.push(format!("button_aliases.{alias} is already an input name"));
+            }
+            if !is_input_name(input) {
+                errors.push(format!("button_aliases.{alias} ({input}) is an unknown input"));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // Lowered once here rather than on every input, as written in any case
+        if let Some(activator) = &mut self.alternative_activator
+            && !self.button_aliases.contains_key(activator)
+        {
+            *activator = activator.to_lowercase();
+        }
+
+        let aliases = &self.button_aliases;
+        let resolve = |name: &mut String| {
+            if let Some(input) = aliases.get(name.as_str()) {
+                *name = input.clone();
+            }
+        };
+        let resolve_chord = |name: &mut String| {
+            if let Some(inputs) = chord_inputs(name) {
+                let inputs = inputs
+                    .into_iter()
+                    .map(|input| aliases.get(input).map_or(input, String::as_str))
+                    .collect::<Vec<_>>();
+                *name = inputs.join("+");
+            } else {
+                resolve(name);
+            }
+        };
+
+        let sets = [&mut self.main, &mut self.alt]
+            .into_iter()
+            .chain(self.gamepads.values_mut().flat_map(|remaps| [&mut remaps.main, &mut remaps.alt]))
+            .chain(self.layers.values_mut());
+        for set in sets {
+            let mut resolved = HashMap::new();
+            for (mut input, mut binding) in std::mem::take(set) {
+                resolve_chord(&mut input);
+                binding.unless.iter_mut().for_each(resolve);
+                if resolved.contains_key(&input) {
+                    errors.push(format!("Input remapped both by name and by alias: {input}"));
+                }
+                resolved.insert(input, binding);
+            }
+            *set = resolved;
+        }
+        self.alternative_activator
+            .iter_mut()
+            .chain(self.chord_activator.iter_mut())
+            .chain(self.layer_activators.values_mut())
+            .for_each(resolve);
+
+        let mut chords = self
+            .remap_sets()
+            .into_iter()
+            .flat_map(|(_, remaps)| remaps.keys())
+            .filter(|input| chord_inputs(input).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        chords.sort();
+        chords.dedup();
+        self.chords = chords;
+
+        if errors.is_empty() { Ok(self) } else { Err(errors) }
+    }
+
+    /// Returns the chords remapped in any set, e.g. `start+south`.
+    pub fn chord_names(&self) -> &[String] {
+        &self.chords
+    }
+
+    /// Returns the keys that a `key_down` remap presses but no `key_up` remap releases.
+    pub fn unreleased_keys(&self) -> Vec<enigo::Key> {
+        let remaps = self
+            .remap_sets()
+            .into_iter()
+            .flat_map(|(_, remaps)| remaps.values())
+            .flat_map(|binding| binding.remap.remaps())
+            .collect::<Vec<_>>();
+        let mut unreleased_keys = Vec::new();
+
+        for remap in &remaps {
+            if let Remap::KeyDown(key) = remap
+                && !remaps.iter().any(|remap| matches!(remap, Remap::KeyUp(released) if released == key))
+                && !unreleased_keys.contains(key)
+            {
+                unreleased_keys.push(*key);
+            }
+        }
+        unreleased_keys
+    }
+
+    /// Returns every remap set with the path it is configured at, e.g. `gamepads.'Name'.alt`.
+    fn remap_sets(&self) -> Vec<(String, &HashMap<String, Binding>)> {
+        let mut gamepads = self.gamepads.iter().collect::<Vec<_>>();
+        gamepads.sort_by_key(|(name, _)| *name);
+        let mut layers = self.layers.iter().collect::<Vec<_>>();
+        layers.sort_by_key(|(name, _)| *name);
+
+        [("main".to_string(), &self.main), ("alt".to_string(), &self.alt)]
+            .into_iter()
+            .chain(gamepads.into_iter().flat_map(|(name, remaps)| {
+                [
+                    (format!("gamepads.'{name}'.main"), &remaps.main),
+                    (format!("gamepads.'{name}'.alt"), &remaps.alt),
+                ]
+            }))
+            .chain(layers.into_iter().map(|(name, remaps)| (format!("layers.{name}"), remaps)))
+            .collect()
+    }
+
+    /// Returns the problems of the stick settings of a controller, or of the top-level ones.
+    fn stick_errors(&self, settings: &StickSettings) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        // Both scale the stick position past the dead zone up to a full push
+        let is_dead_zone_scaled = self.left_stick_dead_zone_shape == DeadZoneShape::Scaled
+            || [Stick::Left, Stick::Right].iter().any(|stick| settings.stick_mode(*stick) == StickMode::Absolute);
+        if settings.left_stick_dead_zone >= 1.0 && is_dead_zone_scaled {
+            errors.push(format!(
+                "left_stick_dead_zone ({}) must be below 1 with a scaled dead zone or an absolute stick",
+                settings.left_stick_dead_zone
+            ));
+        }
+
+        let positive_options = [
+            ("left_stick_dead_zone", settings.left_stick_dead_zone),
+            ("right_stick_trigger_zone", settings.right_stick_trigger_zone),
+            ("right_stick_dead_zone", settings.right_stick_dead_zone),
+        ];
+        for (option, value) in positive_options {
+            if value <= 0.0 {
+                errors.push(format!("{option} ({value}) must be positive"));
             }
         }
 
-        Ok(self)
+        if self.mouse_acceleration_curve == AccelerationCurve::Exponential && settings.mouse_initial_speed <= 0.0 {
+            errors.push(format!(
+                "mouse_initial_speed ({}) must be positive with an exponential mouse_acceleration_curve",
+                settings.mouse_initial_speed
+            ));
+        }
+
+        if settings.right_stick_trigger_zone < settings.right_stick_dead_zone {
+            errors.push(format!(
+                "right_stick_trigger_zone ({}) must be >= right_stick_dead_zone ({})",
+                settings.right_stick_trigger_zone, settings.right_stick_dead_zone
+            ));
+        }
+
+        errors
     }
 
-    /// Retrieves the remap for a given input, considering the active remap set.
+    /// Returns the stick settings of a controller, merging its own ones over the top-level ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `gamepad` - The name of the controller, whose own settings are used if configured.
+    pub fn stick_settings(&self, gamepad: Option<&str>) -> StickSettings {
+        let profile = gamepad.and_then(|name| self.gamepads.get(name));
+        let stick_mode = |stick| match (self.mouse_stick, stick) {
+            (Some(mouse_stick), stick) if mouse_stick == stick => StickMode::Mouse,
+            (Some(_), _) => StickMode::Directional,
+            (None, Stick::Left) => self.left_stick_mode,
+            (None, Stick::Right) => self.right_stick_mode,
+        };
+
+        StickSettings {
+            left_stick_mode: profile
+                .and_then(|profile| profile.left_stick_mode)
+                .unwrap_or_else(|| stick_mode(Stick::Left)),
+            right_stick_mode: profile
+                .and_then(|profile| profile.right_stick_mode)
+                .unwrap_or_else(|| stick_mode(Stick::Right)),
+            left_stick_dead_zone: profile
+                .and_then(|profile| profile.left_stick_dead_zone)
+                .unwrap_or(self.left_stick_dead_zone),
+            right_stick_dead_zone: profile
+                .and_then(|profile| profile.right_stick_dead_zone)
+                .unwrap_or(self.right_stick_dead_zone),
+            right_stick_trigger_zone: profile
+                .and_then(|profile| profile.right_stick_trigger_zone)
+                .unwrap_or(self.right_stick_trigger_zone),
+            mouse_initial_speed: profile
+                .and_then(|profile| profile.mouse_initial_speed)
+                .unwrap_or(self.mouse_initial_speed),
+            mouse_max_speed: profile.and_then(|profile| profile.mouse_max_speed).unwrap_or(self.mouse_max_speed),
+        }
+    }
+
+    /// Retrieves the binding for a given input, considering the active remap set.
     ///
     /// # Arguments
     ///
     /// * `input` - The input name to remap.
     /// * `is_alternative` - Whether to use the alternative remap set.
+    /// * `gamepad` - The name of the controller, whose own remap sets are used if configured.
     ///
     /// # Returns
     ///
-    /// An `Option` containing a reference to the `Remap` if found.
-    pub fn get_remap(&self, input: &str, is_alternative: bool) -> Option<&Remap> {
+    /// An `Option` containing a reference to the `Binding` if found, in the main set if the
+    /// alternative one inherits it.
+    pub fn get_binding(&self, input: &str, is_alternative: bool, gamepad: Option<&str>) -> Option<&Binding> {
+        // A controller configuring only stick settings keeps the top-level sets
+        let profile = gamepad
+            .and_then(|name| self.gamepads.get(name))
+            .filter(|profile| !profile.main.is_empty() || !profile.alt.is_empty());
+        let (main, alt) = match profile {
+            Some(profile) => (&profile.main, &profile.alt),
+            None => (&self.main, &self.alt),
+        };
+
         if is_alternative {
-            self.alt.get(input)
+            alt.get(input).or_else(|| main.get(input).filter(|_| self.alt_inherits_main))
         } else {
-            self.main.get(input)
+            main.get(input)
+        }
+    }
+
+    /// Retrieves the binding for a given input, considering the active named layers first.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input name to remap.
+    /// * `active_layers` - The names of the active layers, the last activated one last.
+    /// * `is_alternative` - Whether to use the alternative remap set below the layers.
+    /// * `gamepad` - The name of the controller, whose own remap sets are used if configured.
+    ///
+    /// # Returns
+    ///
+    /// The binding of the topmost layer remapping the input, or else the one of
+    /// the `main` or `alt` set.
+    pub fn get_layered_binding(
+        &self,
+        input: &str,
+        active_layers: &[String],
+        is_alternative: bool,
+        gamepad: Option<&str>,
+    ) -> Option<&Binding> {
+        active_layers
+            .iter()
+            .rev()
+            .find_map(|layer| self.layers.get(layer)?.get(input))
+            .or_else(|| self.get_binding(input, is_alternative, gamepad))
+    }
+
+    /// Returns the name of the layer an input activates, if it is a layer activator.
+    pub fn layer_activated_by(&self, input: &str) -> Option<&str> {
+        self.layer_activators
+            .iter()
+            .find(|(_, activator)| activator.as_str() == input)
+            .map(|(layer, _)| layer.as_str())
+    }
+
+    /// Returns whether the activator of a named layer is held or toggled.
+    pub fn layer_activator_mode(&self, layer: &str) -> ActivatorMode {
+        self.layer_activator_modes.get(layer).copied().unwrap_or_default()
+    }
+
+    /// Returns how far below `trigger_activation_threshold` a pressed trigger must be let go to be released.
+    pub fn trigger_hysteresis(&self) -> f32 {
+        self.hysteresis.unwrap_or(Self::DEFAULT_TRIGGER_HYSTERESIS)
+    }
+
+    /// Returns how far below `right_stick_trigger_zone` a stick direction must be let go to be released.
+    ///
+    /// A stick back in its dead zone always releases its direction, so the margin
+    /// is at most the gap between both zones, which is also the default.
+    pub fn stick_hysteresis(&self, settings: &StickSettings) -> f32 {
+        let zone_gap = settings.right_stick_trigger_zone - settings.right_stick_dead_zone;
+        self.hysteresis.map_or(zone_gap, |hysteresis| hysteresis.min(zone_gap))
+    }
+
+    /// Returns the resting value of a stick axis, subtracted from its values.
+    pub fn axis_center_offset(&self, axis: StickAxis) -> f32 {
+        match axis {
+            StickAxis::LeftStickX => self.left_stick_center_x,
+            StickAxis::LeftStickY => self.left_stick_center_y,
+            StickAxis::RightStickX => self.right_stick_center_x,
+            StickAxis::RightStickY => self.right_stick_center_y,
+        }
+    }
+
+    /// Checks whether the values of a stick axis are negated.
+    pub fn is_axis_inverted(&self, axis: StickAxis) -> bool {
+        match axis {
+            StickAxis::LeftStickX => self.left_stick_invert_x,
+            StickAxis::LeftStickY => self.left_stick_invert_y,
+            StickAxis::RightStickX => self.right_stick_invert_x,
+            StickAxis::RightStickY => self.right_stick_invert_y,
+        }
+    }
+
+    /// Returns the settings of the backend injecting input, enigo's defaults where not configured.
+    pub fn enigo_settings(&self) -> enigo::Settings {
+        let defaults = enigo::Settings::default();
+        enigo::Settings {
+            linux_delay: self.enigo_linux_delay.unwrap_or(defaults.linux_delay),
+            mac_delay: self.enigo_mac_delay.unwrap_or(defaults.mac_delay),
+            release_keys_when_dropped: self.enigo_release_keys_on_drop,
+            ..defaults
         }
     }
 
     // Default values for configuration settings.
 
+    /// Hysteresis of the analog triggers, so that a trigger held around the threshold does not chatter.
+    const DEFAULT_TRIGGER_HYSTERESIS: f32 = 0.05;
+
     fn default_key_repeat_initial_delay() -> Duration {
         Duration::from_millis(400)
     }
@@ -129,6 +1296,30 @@ impl Config {
         Duration::from_millis(40)
     }
 
+    fn default_double_tap_window() -> Duration {
+        Duration::from_millis(250)
+    }
+
+    fn default_intercept_mode_button() -> bool {
+        true
+    }
+
+    fn default_stick_value_limit() -> f32 {
+        1.0
+    }
+
+    fn default_stick_directions() -> u8 {
+        4
+    }
+
+    fn default_left_stick_mode() -> StickMode {
+        StickMode::Mouse
+    }
+
+    fn default_right_stick_mode() -> StickMode {
+        StickMode::Directional
+    }
+
     fn default_left_stick_poll_interval() -> Duration {
         Duration::from_millis(10)
     }
@@ -137,6 +1328,10 @@ impl Config {
         0.05
     }
 
+    fn default_left_stick_dead_zone_rescale() -> bool {
+        true
+    }
+
     fn default_mouse_initial_speed() -> f32 {
         10.0
     }
@@ -149,6 +1344,42 @@ impl Config {
         30.0
     }
 
+    fn default_stick_scroll_speed() -> f32 {
+        1.0
+    }
+
+    fn default_version() -> u32 {
+        CONFIG_VERSION
+    }
+
+    fn default_scroll_speed() -> f32 {
+        1.0
+    }
+
+    fn default_scroll_ticks_to_reach_max_speed() -> f32 {
+        10.0
+    }
+
+    fn default_left_stick_response_curve() -> f32 {
+        1.0
+    }
+
+    fn default_gyro_sensitivity() -> f32 {
+        10.0
+    }
+
+    fn default_mouse_sensitivity() -> f32 {
+        1.0
+    }
+
+    fn default_mouse_invert_y() -> bool {
+        true
+    }
+
+    fn default_enigo_release_keys_on_drop() -> bool {
+        true
+    }
+
     fn default_right_stick_poll_interval() -> Duration {
         Duration::from_millis(50)
     }
@@ -161,3 +1392,706 @@ impl Config {
         0.1
     }
 }
+
+/// Rewrites the options of a config file written for an older layout, given by its `version`.
+///
+/// Files without a version predate it, and are migrated from the first layout, `0`.
+///
+/// # Returns
+///
+/// Whether an option was rewritten, or an error if an old option cannot be.
+pub fn migrate(table: &mut toml::Table) -> Result<bool, String> {
+    let Some(version) = table.get("version").map_or(Some(0), toml::Value::as_integer) else {
+        // Left to the deserialization to report
+        return Ok(false);
+    };
+    let mut is_migrated = false;
+
+    if version < 1 {
+        // Version 1 split the stick center offsets into one option per axis
+        for stick in ["left_stick", "right_stick"] {
+            let Some(offset) = table.remove(&format!("{stick}_center_offset")) else {
+                continue;
+            };
+            let Some([x, y]) = offset.as_array().and_then(|offset| <[_; 2]>::try_from(offset.clone()).ok()) else {
+                return Err(format!("{stick}_center_offset is not an [x, y] pair"));
+            };
+
+            table.entry(format!("{stick}_center_x")).or_insert(x);
+            table.entry(format!("{stick}_center_y")).or_insert(y);
+            log::warn!("{stick}_center_offset was migrated to {stick}_center_x and {stick}_center_y");
+            is_migrated = true;
+        }
+    }
+
+    if is_migrated {
+        table.insert("version".to_string(), toml::Value::Integer(CONFIG_VERSION.into()));
+    }
+    Ok(is_migrated)
+}
+
+/// The options of a binding besides its remap, which `Binding` flattens so they cannot be listed from it.
+const BINDING_OPTIONS: [&str; 3] = ["unless", "when_window", "feedback"];
+
+/// Returns the options of a config file that are not config options, e.g. misspelled ones, by their path.
+///
+/// Besides the top-level options, the ones of the mapping sets, their bindings
+/// and the feedback and window tables of those are checked.
+pub fn unknown_options(table: &toml::Table) -> Vec<String> {
+    let mut unknown_options = unknown_keys(table, "", field_names::<Config>());
+
+    let mut sets = Vec::new();
+    for set_name in ["main", "alt"] {
+        sets.extend(table.get(set_name).map(|set| (set_name.to_string(), set)));
+    }
+    for (layer, set) in table.get("layers").and_then(toml::Value::as_table).into_iter().flatten() {
+        sets.push((format!("layers.{layer}"), set));
+    }
+    for (gamepad, profile) in subtables(table.get("gamepads")) {
+        let path = format!("gamepads.'{gamepad}'");
+        unknown_options.extend(unknown_keys(profile, &path, field_names::<GamepadProfile>()));
+        for set_name in ["main", "alt"] {
+            sets.extend(profile.get(set_name).map(|set| (format!("{path}.{set_name}"), set)));
+        }
+    }
+
+    let binding_options = [variant_names::<Remap>(), &BINDING_OPTIONS].concat();
+    let mut feedbacks = Vec::from_iter(
+        table
+            .get("activator_feedback")
+            .map(|feedback| ("activator_feedback".to_string(), feedback)),
+    );
+    for (set_path, set) in sets {
+        for (input, binding) in subtables(Some(set)) {
+            let path = format!("{set_path}.{input}");
+            unknown_options.extend(unknown_keys(binding, &path, &binding_options));
+            feedbacks.extend(binding.get("feedback").map(|feedback| (format!("{path}.feedback"), feedback)));
+            if let Some(when_window) = binding.get("when_window").and_then(toml::Value::as_table) {
+                let window_path = format!("{path}.when_window");
+                unknown_options.extend(unknown_keys(when_window, &window_path, field_names::<WindowMatcher>()));
+            }
+        }
+    }
+    for (path, feedback) in feedbacks {
+        if let Some(feedback) = feedback.as_table() {
+            unknown_options.extend(unknown_keys(feedback, &path, field_names::<Feedback>()));
+        }
+    }
+
+    unknown_options.sort();
+    unknown_options
+}
+
+/// Returns the entries of a table whose values are tables themselves, e.g. the bindings of a set.
+fn subtables(value: Option<&toml::Value>) -> impl Iterator<Item = (&String, &toml::Table)> {
+    value
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| Some((key, value.as_table()?)))
+}
+
+/// Returns the paths of the keys of a table that are not among the expected ones.
+fn unknown_keys(table: &toml::Table, path: &str, expected_keys: &[&str]) -> Vec<String> {
+    table
+        .keys()
+        .filter(|key| !expected_keys.contains(&key.as_str()))
+        .map(|key| if path.is_empty() { key.clone() } else { format!("{path}.{key}") })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gamepad_stick_settings() {
+        let config = toml::from_str::<Config>(
+            r#"
+            mouse_stick = 'left'
+            mouse_max_speed = 30.0
+            [gamepads.'Game Controller']
+            left_stick_mode = 'directional'
+            right_stick_dead_zone = 0.2
+            mouse_max_speed = 10.0
+            "#,
+        )
+        .unwrap()
+        .check_error()
+        .unwrap();
+
+        let settings = config.stick_settings(Some("Other Controller"));
+        assert_eq!(settings, config.stick_settings(None));
+        assert_eq!(settings.stick_mode(Stick::Left), StickMode::Mouse);
+        assert_eq!(settings.mouse_max_speed, 30.0);
+
+        // The controller's own settings apply over the top-level ones and `mouse_stick`
+        let settings = config.stick_settings(Some("Game Controller"));
+        assert_eq!(settings.stick_mode(Stick::Left), StickMode::Directional);
+        assert_eq!(settings.stick_mode(Stick::Right), StickMode::Directional);
+        assert_eq!(settings.right_stick_dead_zone, 0.2);
+        assert_eq!(settings.right_stick_trigger_zone, 0.3);
+        assert_eq!(settings.mouse_max_speed, 10.0);
+        assert_eq!(config.stick_hysteresis(&settings), 0.3 - 0.2);
+
+        // Each controller is validated with its merged settings, its own errors only
+        let err = toml::from_str::<Config>(
+            r#"
+            left_stick_dead_zone = 0
+            [gamepads.'Pad']
+            right_stick_dead_zone = 0.5
+            [gamepads.'Other Pad']
+            left_stick_dead_zone = 0.1
+            "#,
+        )
+        .unwrap()
+        .check_error()
+        .unwrap_err();
+        assert_eq!(
+            err,
+            [
+                "left_stick_dead_zone (0) must be positive",
+                "right_stick_trigger_zone (0.3) must be >= right_stick_dead_zone (0.5) in gamepads.'Pad'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_migrate() {
+        let migrate = |config: &str| {
+            let mut table = toml::from_str::<toml::Table>(config).unwrap();
+            migrate(&mut table).map(|is_migrated| (is_migrated, table))
+        };
+
+        let (is_migrated, table) = migrate("left_stick_center_offset = [0.25, -0.5]").unwrap();
+        assert!(is_migrated);
+        let config = toml::Value::Table(table).try_into::<Config>().unwrap();
+        assert_eq!((config.left_stick_center_x, config.left_stick_center_y), (0.25, -0.5));
+        assert_eq!(config.version, CONFIG_VERSION);
+
+        // Current layouts are left as they are
+        assert!(!migrate("left_stick_center_x = 0.25").unwrap().0);
+        assert!(!migrate("version = 1\nleft_stick_center_offset = [0.25, -0.5]").unwrap().0);
+
+        assert_eq!(
+            migrate("right_stick_center_offset = 0.25").unwrap_err(),
+            "right_stick_center_offset is not an [x, y] pair"
+        );
+    }
+
+    #[test]
+    fn test_unknown_options() {
+        let table = toml::from_str::<toml::Table>(
+            "left_stik_dead_zone = 0.2\nversion = 1\nbutton_debounce = '10ms'\nalt_activator = 'start'\n[main]",
+        )
+        .unwrap();
+        assert_eq!(unknown_options(&table), ["alt_activator", "left_stik_dead_zone"]);
+
+        let table = toml::from_str::<toml::Table>(
+            r#"
+            activator_feedback = { beep = true, rumble = 100 }
+
+            [main]
+            south = { seq = ['Space'], unles = 'east', feedback = { beep = true, rumble_strenght = 0.5 } }
+            east = { sync = ['Tab'], when_window = { process = 'game.exe', tilte = 'Game' } }
+
+            [layers.fn]
+            north = { sec = ['Tab'] }
+
+            [gamepads.'Pad']
+            mian = {}
+            alt = { west = { text = 'gg', unless = 'east' } }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            unknown_options(&table),
+            [
+                "activator_feedback.rumble",
+                "gamepads.'Pad'.mian",
+                "layers.fn.north.sec",
+                "main.east.when_window.tilte",
+                "main.south.feedback.rumble_strenght",
+                "main.south.unles"
+            ]
+        );
+
+        let err = toml::from_str::<Config>("version = 2").unwrap().check_error().unwrap_err();
+        assert_eq!(err, ["Config version 2 is newer than the supported 1, update dogmu"]);
+    }
+
+    #[test]
+    fn test_config_template() {
+        // Uncommenting every option of the template gives a valid config naming all of them
+        let uncommented = CONFIG_TEMPLATE
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(option)
+                    if option.starts_with('[')
+                        || option
+                            .split_once(" = ")
+                            .is_some_and(|(key, _)| key.chars().all(|c| c.is_ascii_lowercase() || c == '_')) =>
+                {
+                    option
+                }
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let table = toml::from_str::<toml::Table>(&uncommented).unwrap();
+        assert!(unknown_options(&table).is_empty());
+        let missing_options = field_names::<Config>()
+            .iter()
+            .filter(|field| !table.contains_key(**field))
+            .collect::<Vec<_>>();
+        assert!(missing_options.is_empty(), "{missing_options:?}");
+        toml::from_str::<Config>(&uncommented).unwrap().resolve_aliases().unwrap().check_error().unwrap();
+    }
+
+    #[test]
+    fn test_binding_unless() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { seq = ['Space'], unless = 'left_bumper' }
+            north = { seq = ['Return'] }
+            "#,
+        )
+        .unwrap();
+        let south = config.get_binding("south", false, None).unwrap();
+        let north = config.get_binding("north", false, None).unwrap();
+        let mut held_inputs = HashSet::new();
+
+        assert!(matches!(south.remap, Remap::Seq(_)));
+        assert!(!south.is_suppressed(&held_inputs));
+
+        held_inputs.insert("left_bumper".to_string());
+        assert!(south.is_suppressed(&held_inputs));
+        assert!(!north.is_suppressed(&held_inputs));
+    }
+
+    #[test]
+    fn test_window_matcher() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { seq = ['Space'], when_window = { process = 'Game.exe' } }
+            north = { seq = ['Space'], when_window = { process = 'game.exe', title = 'elden' } }
+            "#,
+        )
+        .unwrap();
+        let matcher = |input| config.get_binding(input, false, None).unwrap().when_window.as_ref().unwrap();
+
+        assert!(matcher("south").matches("game.exe", "Launcher"));
+        assert!(!matcher("south").matches("browser.exe", "Game.exe - Search"));
+        assert!(matcher("north").matches("GAME.EXE", "ELDEN RING"));
+        assert!(!matcher("north").matches("game.exe", "Launcher"));
+    }
+
+    #[test]
+    fn test_gamepad_remaps() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { seq = ['Space'] }
+            [gamepads.'Xbox Controller'.main]
+            south = { seq = ['Return'] }
+            [gamepads.'Stick Controller']
+            mouse_max_speed = 10.0
+            "#,
+        )
+        .unwrap();
+        let resolve = |gamepad| match &config.get_binding("south", false, gamepad).unwrap().remap {
+            Remap::Seq(seq) => seq[0],
+            _ => unreachable!(),
+        };
+
+        assert_eq!(resolve(None), enigo::Key::Space);
+        assert_eq!(resolve(Some("Other Controller")), enigo::Key::Space);
+        assert_eq!(resolve(Some("Xbox Controller")), enigo::Key::Return);
+        assert_eq!(resolve(Some("Stick Controller")), enigo::Key::Space);
+        assert!(config.get_binding("south", true, Some("Xbox Controller")).is_none());
+    }
+
+    #[test]
+    fn test_alt_inherits_main() {
+        let config = r#"
+            alt_inherits_main = true
+            [main]
+            south = { seq = ['Space'] }
+            east = { seq = ['Escape'] }
+            [alt]
+            south = { seq = ['Return'] }
+            [gamepads.'Xbox Controller'.main]
+            north = { seq = ['Tab'] }
+            "#;
+        let config = toml::from_str::<Config>(config).unwrap();
+        let resolve = |input, gamepad| match &config.get_binding(input, true, gamepad)?.remap {
+            Remap::Seq(seq) => Some(seq[0]),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(resolve("south", None), Some(enigo::Key::Return));
+        assert_eq!(resolve("east", None), Some(enigo::Key::Escape));
+        // A controller with its own sets inherits its own main set only
+        assert_eq!(resolve("north", Some("Xbox Controller")), Some(enigo::Key::Tab));
+        assert_eq!(resolve("east", Some("Xbox Controller")), None);
+
+        let config = toml::from_str::<Config>("[main]\neast = { seq = ['Escape'] }").unwrap();
+        assert!(config.get_binding("east", true, None).is_none());
+    }
+
+    #[test]
+    fn test_layers() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { seq = ['Space'] }
+            north = { seq = ['Return'] }
+            [layer_activators]
+            fn = 'left_bumper'
+            nav = 'right_bumper'
+            [layers.fn]
+            south = { seq = ['Escape'] }
+            [layers.nav]
+            south = { seq = ['PageUp'] }
+            west = { seq = ['PageDown'] }
+            "#,
+        )
+        .unwrap();
+        let resolve = |input, layers: &[&str]| {
+            let layers = layers.iter().map(|layer| layer.to_string()).collect::<Vec<_>>();
+            match &config.get_layered_binding(input, &layers, false, None)?.remap {
+                Remap::Seq(seq) => Some(seq[0]),
+                _ => unreachable!(),
+            }
+        };
+
+        assert_eq!(config.layer_activated_by("left_bumper"), Some("fn"));
+        assert_eq!(config.layer_activated_by("south"), None);
+        assert_eq!(resolve("south", &[]), Some(enigo::Key::Space));
+        assert_eq!(resolve("south", &["fn"]), Some(enigo::Key::Escape));
+
+        // The last activated layer wins, and inputs it does not remap fall through
+        assert_eq!(resolve("south", &["fn", "nav"]), Some(enigo::Key::PageUp));
+        assert_eq!(resolve("south", &["nav", "fn"]), Some(enigo::Key::Escape));
+        assert_eq!(resolve("north", &["fn", "nav"]), Some(enigo::Key::Return));
+        assert_eq!(resolve("west", &["fn"]), None);
+        assert!(config.check_error().is_ok());
+
+        let config = toml::from_str::<Config>("[main]\nsouth = { seq = ['Space'] }\n[layer_activators]\nfn = 'south'")
+            .unwrap();
+        assert_eq!(
+            config.check_error().err(),
+            Some(vec![
+                "layer_activators.fn (south) names no layer in layers".to_string(),
+                "layer_activators.fn (south) is remapped in a main set".to_string(),
+            ])
+        );
+
+        let config = toml::from_str::<Config>(
+            r#"
+            chord_activator = ['select', 'start']
+            [layer_activators]
+            fn = 'left_bumper'
+            nav = 'left_bumper'
+            num = 'start'
+            sym = 'south'
+            [layers.fn]
+            [layers.nav]
+            [layers.num]
+            [layers.sym]
+            [gamepads.'Pad'.main]
+            south = { seq = ['Space'] }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.check_error().err(),
+            Some(vec![
+                "layer_activators.nav (left_bumper) already activates layer fn".to_string(),
+                "layer_activators.num (start) cannot be in chord_activator".to_string(),
+                "layer_activators.sym (south) is remapped in a main set".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_stick_mode() {
+        let config = toml::from_str::<Config>("").unwrap();
+        assert_eq!(config.stick_settings(None).stick_mode(Stick::Left), StickMode::Mouse);
+        assert_eq!(config.stick_settings(None).stick_mode(Stick::Right), StickMode::Directional);
+
+        let config = toml::from_str::<Config>(
+            "left_stick_mode = 'directional'\nright_stick_mode = 'mouse'",
+        )
+        .unwrap();
+        assert_eq!(config.stick_settings(None).stick_mode(Stick::Left), StickMode::Directional);
+        assert_eq!(config.stick_settings(None).stick_mode(Stick::Right), StickMode::Mouse);
+
+        // The mouse stick switches both sticks at once, whatever their modes
+        let config = toml::from_str::<Config>("mouse_stick = 'right'\nright_stick_mode = 'scroll'").unwrap();
+        assert_eq!(config.stick_settings(None).stick_mode(Stick::Left), StickMode::Directional);
+        assert_eq!(config.stick_settings(None).stick_mode(Stick::Right), StickMode::Mouse);
+        let config = toml::from_str::<Config>("mouse_stick = 'left'\nleft_stick_mode = 'absolute'").unwrap();
+        assert_eq!(config.stick_settings(None).stick_mode(Stick::Left), StickMode::Mouse);
+        assert_eq!(config.stick_settings(None).stick_mode(Stick::Right), StickMode::Directional);
+    }
+
+    #[test]
+    fn test_axis_inversion() {
+        let config = toml::from_str::<Config>("").unwrap();
+        assert!(!config.is_axis_inverted(StickAxis::LeftStickY));
+
+        let config = toml::from_str::<Config>("right_stick_invert_y = true").unwrap();
+        assert!(config.is_axis_inverted(StickAxis::RightStickY));
+        assert!(!config.is_axis_inverted(StickAxis::RightStickX));
+        assert!(!config.is_axis_inverted(StickAxis::LeftStickY));
+
+        let config = toml::from_str::<Config>("left_stick_center_x = 0.03\nleft_stick_center_y = -0.01").unwrap();
+        assert_eq!(config.axis_center_offset(StickAxis::LeftStickX), 0.03);
+        assert_eq!(config.axis_center_offset(StickAxis::LeftStickY), -0.01);
+        assert_eq!(config.axis_center_offset(StickAxis::RightStickX), 0.);
+    }
+
+    #[test]
+    fn test_enigo_settings() {
+        let settings = toml::from_str::<Config>("").unwrap().enigo_settings();
+        assert_eq!((settings.linux_delay, settings.mac_delay, settings.release_keys_when_dropped), (12, 20, true));
+
+        let config = "enigo_linux_delay = 0\nenigo_mac_delay = 5\nenigo_release_keys_on_drop = false";
+        let settings = toml::from_str::<Config>(config).unwrap().enigo_settings();
+        assert_eq!((settings.linux_delay, settings.mac_delay, settings.release_keys_when_dropped), (0, 5, false));
+    }
+
+    #[test]
+    fn test_repeat_forms() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { repeat = 'Return' }
+            north = { repeat = { key = 'PageUp', sub_delay = '20ms' } }
+            "#,
+        )
+        .unwrap();
+        let repeat = |input| match &config.get_binding(input, false, None).unwrap().remap {
+            Remap::Repeat(repeat) => repeat,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(repeat("south").key, enigo::Key::Return);
+        assert_eq!(repeat("south").sub_delay, None);
+        assert_eq!(repeat("north").key, enigo::Key::PageUp);
+        assert_eq!(repeat("north").initial_delay, None);
+        assert_eq!(repeat("north").sub_delay, Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn test_repeat_acceleration() {
+        let config = r#"
+            [main]
+            south = { repeat = 'Return' }
+            north = { repeat = { key = 'PageUp', sub_delay_min = '10ms', acceleration = 0.5 } }
+            west = { repeat = { key = 'PageUp', acceleration = 0.5 } }
+            east = { repeat = { key = 'PageUp', sub_delay_min = '10ms', acceleration = 0 } }
+            "#;
+        let config = toml::from_str::<Config>(config).unwrap();
+        let repeat = |input| match &config.get_binding(input, false, None).unwrap().remap {
+            Remap::Repeat(repeat) => repeat,
+            _ => unreachable!(),
+        };
+        let ms = Duration::from_millis;
+
+        assert_eq!(repeat("south").delay_after(5, ms(40)), ms(40));
+        let delays = (0..4).map(|repeats| repeat("north").delay_after(repeats, ms(40))).collect::<Vec<_>>();
+        assert_eq!(delays, [ms(40), ms(20), ms(10), ms(10)]);
+
+        let errors = config.check_error().unwrap_err();
+        assert!(errors.iter().any(|error| error.contains("Accelerating repeat without sub_delay_min")));
+        assert!(errors.iter().any(|error| error.contains("Repeat acceleration out of 0..1")));
+    }
+
+    #[test]
+    fn test_check_error() {
+        let check = |config: &str| toml::from_str::<Config>(config).unwrap().check_error().map(|_| ());
+
+        assert_eq!(check("[main]\nsouth = { seq = ['Space'], unless = 'left_bumper' }"), Ok(()));
+        // Every problem is reported at once, with the option and its value
+        assert_eq!(
+            check("right_stick_trigger_zone = 0.05\nleft_stick_dead_zone = 0\nstick_directions = 6"),
+            Err(vec![
+                "left_stick_dead_zone (0) must be positive".to_string(),
+                "right_stick_trigger_zone (0.05) must be >= right_stick_dead_zone (0.1)".to_string(),
+                "stick_directions (6) must be 4 or 8".to_string(),
+            ])
+        );
+        assert_eq!(
+            check(
+                r#"
+                alternative_activator = 'left_bumpr'
+                [main]
+                north = { command = '' }
+                south = { command = 'echo "hi' }
+                west = { seq = ['Space'], unless = 'select_' }
+                east = { seq = ['Space'], when_window = {} }
+                [gamepads.'Pad'.alt]
+                east = { hold_tap = { tap = { seq = [] }, hold = { seq = ['Space'] }, hold_duration = '1s' } }
+                west = { turbo = { inner = { turbo = { inner = { seq = ['Space'] }, interval = '50ms' } }, interval = '50ms' } }
+                "#
+            ),
+            Err(vec![
+                "alternative_activator (left_bumpr) is an unknown input".to_string(),
+                "Empty window condition in main.east.when_window".to_string(),
+                "Empty command in main.north".to_string(),
+                "Unparseable command: echo \"hi in main.south".to_string(),
+                "Unknown input in main.west.unless: select_".to_string(),
+                "Empty key list in gamepads.'Pad'.alt.east".to_string(),
+                "Nested turbo in gamepads.'Pad'.alt.west".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unreleased_keys() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { key_down = 'Shift' }
+            east = { key_up = 'Shift' }
+            north = { hold_tap = { tap = { key_down = 'Control' }, hold = { key_down = 'Alt' }, hold_duration = '1s' } }
+
+            [layers.fn]
+            west = { key_up = 'Alt' }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.unreleased_keys(), [enigo::Key::Control]);
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let config = toml::from_str::<Config>("").unwrap();
+        assert_eq!(config.trigger_hysteresis(), 0.05);
+        assert_eq!(config.stick_hysteresis(&config.stick_settings(None)), 0.3 - 0.1);
+
+        // The stick margin never goes past the dead zone
+        let config = toml::from_str::<Config>("hysteresis = 0.5").unwrap();
+        assert_eq!(config.trigger_hysteresis(), 0.5);
+        assert_eq!(config.stick_hysteresis(&config.stick_settings(None)), 0.3 - 0.1);
+
+        let err = toml::from_str::<Config>("hysteresis = -0.1").unwrap().check_error().unwrap_err();
+        assert_eq!(err, ["hysteresis (-0.1) must be between 0 and 1"]);
+    }
+
+    #[test]
+    fn test_button_aliases() {
+        let config = toml::from_str::<Config>(
+            r#"
+            alternative_activator = 'l1'
+            [button_aliases]
+            cross = 'south'
+            l1 = 'left_bumper'
+            r1 = 'right_bumper'
+            [main]
+            cross = { seq = ['Space'], unless = 'r1' }
+            "#,
+        )
+        .unwrap()
+        .resolve_aliases()
+        .unwrap();
+        assert_eq!(config.alternative_activator.as_deref(), Some("left_bumper"));
+        let binding = config.get_binding("south", false, None).unwrap();
+        assert_eq!(binding.unless.as_deref(), Some("right_bumper"));
+
+        // The activator is matched regardless of case, unlike the aliases
+        let config = toml::from_str::<Config>("alternative_activator = 'Start'").unwrap().resolve_aliases().unwrap();
+        assert_eq!(config.alternative_activator.as_deref(), Some("start"));
+
+        let resolve =
+            |config: &str| toml::from_str::<Config>(config).unwrap().resolve_aliases().map(|_| ());
+        assert_eq!(
+            resolve("[button_aliases]\nnorth = 'south'\nx = 'square'"),
+            Err(vec![
+                "button_aliases.north is already an input name".to_string(),
+                "button_aliases.x (square) is an unknown input".to_string(),
+            ])
+        );
+        assert_eq!(
+            resolve("[button_aliases]\ncross = 'south'\n[main]\ncross = { seq = ['Space'] }\nsouth = { seq = ['Tab'] }"),
+            Err(vec!["Input remapped both by name and by alias: south".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_command_forms() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { command = 'notify-send hi' }
+            north = { command = { cmdline = 'make', cwd = '/tmp', env = { CC = 'clang' } } }
+            west = { command = { on_press = 'mute', on_release = 'unmute' } }
+            east = { command = { on_release = ' ' } }
+            "#,
+        )
+        .unwrap();
+        let command = |input| match &config.get_binding(input, false, None).unwrap().remap {
+            Remap::Command(command) => command,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(command("south").cmdline(true), Some("notify-send hi"));
+        assert_eq!(command("south").cmdline(false), None);
+        assert_eq!(command("south").cwd, None);
+        assert!(command("south").env.is_empty());
+        assert_eq!(command("north").cmdline(true), Some("make"));
+        assert_eq!(command("north").cwd.as_deref(), Some("/tmp"));
+        assert_eq!(command("north").env["CC"], "clang");
+        assert_eq!(command("west").cmdline(true), Some("mute"));
+        assert_eq!(command("west").cmdline(false), Some("unmute"));
+
+        // Absent and empty commands do nothing
+        assert_eq!(command("east").cmdline(true), None);
+        assert_eq!(command("east").cmdline(false), None);
+    }
+
+    #[test]
+    fn test_swap_layers_parse() {
+        let config = toml::from_str::<Config>("[main]\nmode = { swap_layers = {} }").unwrap();
+        assert!(matches!(config.get_binding("mode", false, None).unwrap().remap, Remap::SwapLayers));
+    }
+
+    #[test]
+    fn test_binding_feedback() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { seq = ['Space'], feedback = { rumble_ms = 100, rumble_strength = 0.5, beep = true } }
+            north = { seq = ['Return'], feedback = { beep = true } }
+            west = { seq = ['Tab'] }
+            "#,
+        )
+        .unwrap();
+
+        let south = config.get_binding("south", false, None).unwrap().feedback.unwrap();
+        assert_eq!(south.rumble_ms, 100);
+        assert_eq!(south.rumble_strength, 0.5);
+        assert!(south.beep);
+
+        let north = config.get_binding("north", false, None).unwrap().feedback.unwrap();
+        assert_eq!(north.rumble_ms, 0);
+        assert_eq!(north.rumble_strength, 1.0);
+        assert!(north.beep);
+
+        assert!(config.get_binding("west", false, None).unwrap().feedback.is_none());
+
+        let config = toml::from_str::<Config>(
+            "alternative_activator = 'select'\nactivator_feedback = { rumble_ms = 50, rumble_strength = 2 }",
+        )
+        .unwrap();
+        assert_eq!(config.activator_feedback.unwrap().rumble_ms, 50);
+        assert_eq!(
+            config.check_error().err(),
+            Some(vec!["Rumble strength out of 0..1 in activator_feedback".to_string()])
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+use gilrs::{Event, EventType, Gilrs};
+
+use crate::feedback::Rumble;
+use crate::names::{BUTTON_INPUT_NAMES, get_button_input_name, is_button_input_name};
+
+/// How long the gilrs source waits for a controller event before playing queued rumbles.
+const FEEDBACK_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Identifies a controller among the connected ones.
+pub type PadId = usize;
+
+/// A stick axis whose position is tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StickAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+}
+
+/// An analog trigger whose pull is tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl Trigger {
+    /// Returns the input name of the button the trigger acts as.
+    pub fn input_name(self) -> &'static str {
+        match self {
+            Trigger::LeftTrigger => "left_trigger",
+            Trigger::RightTrigger => "right_trigger",
+        }
+    }
+}
+
+/// A controller event, as handled by the remapping pipeline.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputEvent {
+    /// A controller was connected, with the name it reports if known.
+    Connected { name: Option<String> },
+    /// A controller was disconnected.
+    Disconnected,
+    /// A button, identified by its input name, was pressed.
+    ButtonPressed { button: String },
+    /// A button, identified by its input name, was released.
+    ButtonReleased { button: String },
+    /// A stick axis moved to `value`, between `-1` and `1`.
+    AxisChanged { axis: StickAxis, value: f32 },
+    /// An analog trigger was pulled to `value`, between `0` and `1`.
+    TriggerChanged { trigger: Trigger, value: f32 },
+    /// The controller turned by `x` degrees to the right and `y` degrees upwards.
+    ///
+    /// gilrs does not read motion sensors, so only replays produce this event.
+    GyroMoved { x: f32, y: f32 },
+}
+
+impl InputEvent {
+    /// Converts a gilrs event, if it is relevant to remapping.
+    fn from_gilrs(event: EventType) -> Option<Self> {
+        match event {
+            EventType::Connected => Some(InputEvent::Connected { name: None }),
+            EventType::Disconnected => Some(InputEvent::Disconnected),
+            EventType::ButtonPressed(button, code) => {
+                let input_name = get_button_input_name(button, code.into_u32());
+                if !BUTTON_INPUT_NAMES.iter().any(|(named_button, _)| *named_button == button) {
+                    log::debug!("Received the unnamed button {input_name}");
+                }
+                Some(InputEvent::ButtonPressed { button: input_name })
+            }
+            EventType::ButtonReleased(button, code) => Some(InputEvent::ButtonReleased {
+                button: get_button_input_name(button, code.into_u32()),
+            }),
+            EventType::ButtonChanged(button, value, ..) => {
+                let trigger = match button {
+                    gilrs::Button::LeftTrigger2 => Trigger::LeftTrigger,
+                    gilrs::Button::RightTrigger2 => Trigger::RightTrigger,
+                    _ => return None,
+                };
+                Some(InputEvent::TriggerChanged { trigger, value })
+            }
+            EventType::AxisChanged(axis, value, ..) => {
+                let axis = match axis {
+                    gilrs::Axis::LeftStickX => StickAxis::LeftStickX,
+                    gilrs::Axis::LeftStickY => StickAxis::LeftStickY,
+                    gilrs::Axis::RightStickX => StickAxis::RightStickX,
+                    gilrs::Axis::RightStickY => StickAxis::RightStickY,
+                    // Some drivers report the triggers as axes only
+                    gilrs::Axis::LeftZ => {
+                        return Some(InputEvent::TriggerChanged { trigger: Trigger::LeftTrigger, value });
+                    }
+                    gilrs::Axis::RightZ => {
+                        return Some(InputEvent::TriggerChanged { trigger: Trigger::RightTrigger, value });
+                    }
+                    _ => return None,
+                };
+                Some(InputEvent::AxisChanged { axis, value })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A source of controller events driving the remapping.
+pub trait EventSource {
+    /// Blocks until the next event is available.
+    ///
+    /// # Returns
+    ///
+    /// The next event and the controller it comes from, or `None` once the
+    /// source is exhausted.
+    fn next_event(&mut self) -> Option<(PadId, InputEvent)>;
+}
+
+/// Events read from the connected controllers.
+pub struct GilrsSource {
+    gilrs: Gilrs,
+    rumble: Rumble,
+    /// Events to return before reading new ones.
+    pending: VecDeque<(PadId, InputEvent)>,
+}
+
+impl GilrsSource {
+    /// Opens the gamepad context.
+    ///
+    /// The controllers connected beforehand are reported as connected first.
+    pub fn new() -> Result<Self, Box<gilrs::Error>> {
+        let gilrs = Gilrs::new().map_err(Box::new)?;
+        let pending = gilrs
+            .gamepads()
+            .map(|(id, gamepad)| {
+                let name = Some(gamepad.name().to_string());
+                (id.into(), InputEvent::Connected { name })
+            })
+            .collect();
+
+        Ok(Self {
+            gilrs,
+            rumble: Rumble::new(),
+            pending,
+        })
+    }
+}
+
+impl EventSource for GilrsSource {
+    fn next_event(&mut self) -> Option<(PadId, InputEvent)> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+
+        loop {
+            self.rumble.update(&mut self.gilrs);
+
+            if let Some(Event { id, event, .. }) = self.gilrs.next_event_blocking(Some(FEEDBACK_POLL_INTERVAL)) {
+                match InputEvent::from_gilrs(event) {
+                    Some(InputEvent::Connected { .. }) => {
+                        let name = Some(self.gilrs.gamepad(id).name().to_string());
+                        return Some((id.into(), InputEvent::Connected { name }));
+                    }
+                    Some(event) => return Some((id.into(), event)),
+                    None => (),
+                }
+            }
+        }
+    }
+}
+
+/// A timestamped line of a replay file.
+#[derive(Debug, serde::Deserialize)]
+struct ReplayLine {
+    /// Time of the event in milliseconds, counted from the start of the replay.
+    at_ms: u64,
+    /// Controller the event comes from.
+    #[serde(default)]
+    pad: PadId,
+    #[serde(flatten)]
+    event: InputEvent,
+}
+
+/// Events read from a replay file, one JSON object per line, fired at their timestamps.
+///
+/// ```json
+/// {"at_ms": 0, "type": "button_pressed", "button": "south"}
+/// {"at_ms": 100, "type": "axis_changed", "axis": "left_stick_x", "value": 0.5}
+/// ```
+pub struct ReplaySource {
+    lines: Vec<ReplayLine>,
+    next: usize,
+    start: Instant,
+}
+
+impl ReplaySource {
+    /// Parses a replay from a reader.
+    ///
+    /// # Returns
+    ///
+    /// The source, or an error naming the first invalid line.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, String> {
+        let mut lines = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|err| err.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line = serde_json::from_str::<ReplayLine>(&line)
+                .map_err(|err| format!("Invalid replay event on line {}: {err}", index + 1))?;
+            if let InputEvent::ButtonPressed { button } | InputEvent::ButtonReleased { button } = &line.event
+                && !is_button_input_name(button)
+            {
+                return Err(format!("Unknown button on line {}: {button}", index + 1));
+            }
+            lines.push(line);
+        }
+
+        Ok(Self {
+            lines,
+            next: 0,
+            start: Instant::now(),
+        })
+    }
+
+    /// Opens a replay file.
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|err| format!("{path}: {err}"))?;
+        Self::from_reader(std::io::BufReader::new(file))
+    }
+
+    /// Returns the events with their time from the start, for tests to fire them on a paused clock.
+    #[cfg(test)]
+    pub fn into_timed_events(self) -> Vec<(Duration, PadId, InputEvent)> {
+        self.lines
+            .into_iter()
+            .map(|line| (Duration::from_millis(line.at_ms), line.pad, line.event))
+            .collect()
+    }
+}
+
+impl EventSource for ReplaySource {
+    fn next_event(&mut self) -> Option<(PadId, InputEvent)> {
+        let line = self.lines.get(self.next)?;
+        self.next += 1;
+
+        let at = self.start + Duration::from_millis(line.at_ms);
+        std::thread::sleep(at.saturating_duration_since(Instant::now()));
+        Some((line.pad, line.event.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_parse() {
+        let replay = r#"
+{"at_ms": 0, "type": "connected", "name": "Xbox Controller"}
+{"at_ms": 0, "type": "button_pressed", "button": "south"}
+
+{"at_ms": 10, "type": "axis_changed", "axis": "left_stick_x", "value": 0.5}
+{"at_ms": 20, "type": "button_released", "button": "south"}
+{"at_ms": 20, "pad": 1, "type": "connected", "name": "Xbox Controller"}
+"#;
+        let mut source = ReplaySource::from_reader(replay.as_bytes()).unwrap();
+
+        assert_eq!(
+            source.next_event(),
+            Some((
+                0,
+                InputEvent::Connected {
+                    name: Some("Xbox Controller".to_string())
+                }
+            ))
+        );
+        assert_eq!(
+            source.next_event(),
+            Some((
+                0,
+                InputEvent::ButtonPressed {
+                    button: "south".to_string()
+                }
+            ))
+        );
+        assert_eq!(
+            source.next_event(),
+            Some((
+                0,
+                InputEvent::AxisChanged {
+                    axis: StickAxis::LeftStickX,
+                    value: 0.5
+                }
+            ))
+        );
+        assert_eq!(
+            source.next_event(),
+            Some((
+                0,
+                InputEvent::ButtonReleased {
+                    button: "south".to_string()
+                }
+            ))
+        );
+        assert_eq!(
+            source.next_event(),
+            Some((
+                1,
+                InputEvent::Connected {
+                    name: Some("Xbox Controller".to_string())
+                }
+            ))
+        );
+        assert_eq!(source.next_event(), None);
+        assert!(source.start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_replay_invalid_line() {
+        let replay = "{\"at_ms\": 0, \"type\": \"connected\"}\n{\"at_ms\": 5, \"type\": \"jump\"}\n";
+        let err = ReplaySource::from_reader(replay.as_bytes()).err().unwrap();
+        assert!(err.starts_with("Invalid replay event on line 2"), "{err}");
+
+        let replay = "{\"at_ms\": 0, \"type\": \"button_pressed\", \"button\": \"jump\"}\n";
+        let err = ReplaySource::from_reader(replay.as_bytes()).err().unwrap();
+        assert_eq!(err, "Unknown button on line 1: jump");
+    }
+}
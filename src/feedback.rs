@@ -0,0 +1,110 @@
+use std::sync::OnceLock;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::{Duration, Instant};
+
+use gilrs::ff::{BaseEffect, BaseEffectType, EffectBuilder, Repeat, Replay, Ticks};
+use gilrs::Gilrs;
+
+use crate::config::Feedback;
+use crate::event::PadId;
+
+static RUMBLE_SENDER: OnceLock<Sender<(PadId, Feedback)>> = OnceLock::new();
+
+#[cfg(target_os = "windows")]
+#[link(name = "user32")]
+unsafe extern "system" {
+    fn MessageBeep(u_type: u32) -> i32;
+}
+
+/// Plays the system beep.
+pub fn beep() {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        MessageBeep(0xFFFFFFFF);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        use std::io::Write;
+
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+    }
+}
+
+/// Plays the feedback of a binding that just fired on a controller.
+///
+/// The beep is played right away, while the rumble is queued for the main loop,
+/// which owns the gamepads, to play it on the controller.
+pub fn play(pad: PadId, feedback: &Feedback) {
+    if feedback.beep {
+        beep();
+    }
+
+    if feedback.rumble_ms > 0
+        && let Some(sender) = RUMBLE_SENDER.get()
+    {
+        let _ = sender.send((pad, *feedback));
+    }
+}
+
+/// Plays queued rumbles on the gamepads owned by the main loop.
+pub struct Rumble {
+    receiver: Receiver<(PadId, Feedback)>,
+    effects: Vec<(Instant, gilrs::ff::Effect)>,
+}
+
+impl Rumble {
+    /// Creates the rumble player and starts accepting rumbles from `play`.
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+        let _ = RUMBLE_SENDER.set(sender);
+
+        Self {
+            receiver,
+            effects: Vec::new(),
+        }
+    }
+
+    /// Plays queued rumbles on their controllers and releases the ones that are over.
+    ///
+    /// # Arguments
+    ///
+    /// * `gilrs` - The gamepad context the effects are created in.
+    pub fn update(&mut self, gilrs: &mut Gilrs) {
+        // Effects stop playing once dropped, so they are only released when over
+        self.effects.retain(|(end, _)| Instant::now() < *end);
+
+        for (pad, feedback) in self.receiver.try_iter().collect::<Vec<_>>() {
+            // Controllers gone since the feedback was queued are skipped
+            let Some(gamepad_id) = gilrs.gamepads().map(|(id, _)| id).find(|id| PadId::from(*id) == pad) else {
+                continue;
+            };
+            if !gilrs.gamepad(gamepad_id).is_ff_supported() {
+                continue;
+            }
+
+            let duration = Ticks::from_ms(feedback.rumble_ms);
+            let magnitude = (feedback.rumble_strength.clamp(0., 1.) * f32::from(u16::MAX)) as u16;
+            let effect = EffectBuilder::new()
+                .add_effect(BaseEffect {
+                    kind: BaseEffectType::Strong { magnitude },
+                    scheduling: Replay {
+                        play_for: duration,
+                        ..Default::default()
+                    },
+                    envelope: Default::default(),
+                })
+                .repeat(Repeat::For(duration))
+                .gamepads(&[gamepad_id])
+                .finish(gilrs);
+
+            if let Ok(effect) = effect
+                && effect.play().is_ok()
+            {
+                let end = Instant::now() + Duration::from_millis(feedback.rumble_ms.into());
+                self.effects.push((end, effect));
+            }
+        }
+    }
+}
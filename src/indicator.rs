@@ -0,0 +1,32 @@
+use crate::config::LayerIndicator;
+
+/// Shows which layer became active, as configured by `layer_indicator`.
+///
+/// Desktop notifications are sent with `notify-send` on Linux and play the
+/// system beep on Windows; elsewhere they fall back to the log.
+///
+/// # Arguments
+///
+/// * `indicator` - How the change is shown.
+/// * `layer` - The name of the active layer: a named layer, `alt` or `main`.
+pub fn show_layer(indicator: LayerIndicator, layer: &str) {
+    match indicator {
+        LayerIndicator::None => (),
+        LayerIndicator::Log => log::info!("Switched to the {layer} layer"),
+        LayerIndicator::Notification => {
+            log::info!("Switched to the {layer} layer");
+
+            #[cfg(all(target_os = "linux", not(test)))]
+            if let Err(err) = std::process::Command::new("notify-send")
+                .args(["--app-name", env!("CARGO_PKG_NAME"), "--expire-time", "1000"])
+                .arg(format!("{layer} layer"))
+                .spawn()
+            {
+                log::warn!("Cannot show the layer notification: {err}");
+            }
+
+            #[cfg(all(target_os = "windows", not(test)))]
+            crate::feedback::beep();
+        }
+    }
+}
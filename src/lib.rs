@@ -0,0 +1,3767 @@
+//! Remapping of controller inputs to keyboard and mouse inputs, run by the `dogmu` binary.
+
+mod alert;
+mod atomic_f32;
+mod backend;
+mod calibration;
+mod cli;
+mod config;
+mod event;
+mod feedback;
+mod indicator;
+mod names;
+mod power;
+mod stick;
+mod window;
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use enigo::Direction;
+use single_instance::SingleInstance;
+use tokio::time::Instant;
+
+use crate::atomic_f32::*;
+use crate::backend::{InputCommand, InputSender};
+use crate::cli::Action;
+use crate::config::*;
+use crate::event::{EventSource, InputEvent, PadId, StickAxis, Trigger};
+use crate::stick::Stick;
+
+/// A stick position, or another pair of axis values read together.
+///
+/// Both axes are packed into a single `AtomicU64`, so that a loop reading the
+/// pair never sees a new `x` along with a `y` older than the one stored with it.
+/// Like `AtomicF32`, non-finite values are stored as zero.
+struct Coordinate {
+    storage: AtomicU64,
+}
+
+impl Coordinate {
+    const fn new() -> Self {
+        Self {
+            storage: AtomicU64::new(0),
+        }
+    }
+
+    fn pack(x: f32, y: f32) -> u64 {
+        (u64::from(finite_bits(x)) << 32) | u64::from(finite_bits(y))
+    }
+
+    fn unpack(bits: u64) -> (f32, f32) {
+        (f32::from_bits((bits >> 32) as u32), f32::from_bits(bits as u32))
+    }
+
+    fn reset(&self) {
+        self.storage.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns both axes, as stored together.
+    fn load_pair(&self) -> (f32, f32) {
+        Self::unpack(self.storage.load(Ordering::Relaxed))
+    }
+
+    /// Resets both axes to zero, returning their previous values.
+    fn take_pair(&self) -> (f32, f32) {
+        Self::unpack(self.storage.swap(0, Ordering::Relaxed))
+    }
+
+    fn store_pair(&self, x: f32, y: f32) {
+        self.storage.store(Self::pack(x, y), Ordering::Relaxed);
+    }
+
+    /// Replaces one axis, keeping the other even if it is stored at the same time.
+    fn update(&self, update: impl Fn((f32, f32)) -> (f32, f32)) {
+        let _ = self.storage.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            let (x, y) = update(Self::unpack(bits));
+            Some(Self::pack(x, y))
+        });
+    }
+
+    fn store_x(&self, x: f32) {
+        self.update(|(_, y)| (x, y));
+    }
+
+    fn store_y(&self, y: f32) {
+        self.update(|(x, _)| (x, y));
+    }
+
+    fn distance_to_origin(&self) -> f32 {
+        let (x, y) = self.load_pair();
+        (x * x + y * y).sqrt()
+    }
+}
+
+/// The pixels a stick moved the mouse by since the last movement, sent at once
+/// every `mouse_move_flush_interval`.
+#[derive(Default)]
+struct MouseBatch {
+    pixels: (i32, i32),
+    /// The time polled since the last movement.
+    elapsed: Duration,
+}
+
+impl MouseBatch {
+    /// Adds the pixels of a poll, and returns the ones to move the mouse by now, if any.
+    ///
+    /// The pixels are kept until the flush interval is over, or the stick stops
+    /// so that the mouse never lags behind a stick at rest.
+    fn add(&mut self, pixels: (i32, i32), is_moving: bool) -> Option<(i32, i32)> {
+        self.pixels = (self.pixels.0 + pixels.0, self.pixels.1 + pixels.1);
+        let is_due = get_config()
+            .mouse_move_flush_interval
+            .is_none_or(|interval| !is_moving || self.elapsed >= interval);
+        if !is_due {
+            return None;
+        }
+
+        self.elapsed = Duration::ZERO;
+        Some(std::mem::take(&mut self.pixels)).filter(|pixels| *pixels != (0, 0))
+    }
+}
+
+/// Movements accumulated on two axes, each taken on its own once applied.
+struct Delta {
+    x: AtomicF32,
+    y: AtomicF32,
+}
+
+impl Delta {
+    const fn new() -> Self {
+        Self {
+            x: AtomicF32::new(),
+            y: AtomicF32::new(),
+        }
+    }
+
+    fn reset(&self) {
+        self.x.reset();
+        self.y.reset();
+    }
+}
+
+/// The state of an input bound to a remap choosing its action from the way it is pressed.
+enum TapState {
+    /// The action is not known yet; the task decides it once the wait is over.
+    Pending {
+        handle: tokio::task::JoinHandle<()>,
+        is_released: bool,
+    },
+    /// The chosen action was pressed and is released with the input.
+    Firing(&'static Remap),
+}
+
+/// The state of a controller, kept apart from the others so that several
+/// players can use their own controllers at the same time.
+struct Pad {
+    /// The id of the controller, which feedback is played on.
+    id: PadId,
+    /// The name reported by the controller, selecting its remap sets and stick settings if configured.
+    name: Mutex<Option<String>>,
+    is_alternative_active: AtomicBool,
+    /// Whether every input of the `chord_activator` is held.
+    is_chord_active: AtomicBool,
+    /// The named layers whose activator is held, the last activated one last.
+    active_layers: Mutex<Vec<String>>,
+    left_stick_coord: Coordinate,
+    right_stick_coord: Coordinate,
+    left_stick_peak: Coordinate,
+    right_stick_peak: Coordinate,
+    /// How far the analog triggers are pulled, between `0` and `1`.
+    left_trigger_pull: AtomicF32,
+    right_trigger_pull: AtomicF32,
+    /// The controller turns since the mouse last moved, in degrees.
+    gyro_delta: Delta,
+    /// The fractions of a pixel the mouse moved by a stick still has to move.
+    left_stick_mouse_remainder: Coordinate,
+    right_stick_mouse_remainder: Coordinate,
+    /// Wakes the stick loops waiting for the stick to move.
+    left_stick_notify: tokio::sync::Notify,
+    right_stick_notify: tokio::sync::Notify,
+    held_inputs: Mutex<HashSet<String>>,
+    suppressed_inputs: Mutex<HashSet<String>>,
+    /// The inputs held while their binding is released, overridden by their opposite direction.
+    overridden_inputs: Mutex<HashSet<String>>,
+    /// The chords pressed, released as soon as one of their inputs is.
+    active_chords: Mutex<Vec<String>>,
+    /// The inputs held as part of a chord, whose own bindings are skipped until they are released.
+    chord_inputs: Mutex<HashSet<String>>,
+    /// The keys latched down by `toggle` remaps, by input name.
+    latched_toggles: Mutex<HashMap<String, &'static [enigo::Key]>>,
+    /// The inputs bound to a `double_tap` or `hold_tap` remap that are being pressed.
+    taps: Mutex<HashMap<String, TapState>>,
+    disconnect_abort_handle: tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    /// The releases delayed by `button_debounce`, by input name, canceled by a press bouncing back.
+    debounced_releases: tokio::sync::Mutex<HashMap<String, tokio::task::JoinHandle<()>>>,
+    /// The tasks repeating a remap while its input is held, by input name.
+    ///
+    /// They are found by the input rather than by the remap, whose set may have
+    /// changed by the time the input is released.
+    repeat_abort_handles: tokio::sync::Mutex<HashMap<String, tokio::task::JoinHandle<()>>>,
+    /// The tasks of `turbo` and `analog` remaps, apart from the repeat ones so that a repeating remap can be turboed.
+    turbo_abort_handles: tokio::sync::Mutex<HashMap<String, tokio::task::JoinHandle<()>>>,
+    /// Whether the state was reset after a disconnect, so that a reconnect starts afresh.
+    is_reset: AtomicBool,
+    /// Whether the stick loops of the controller were started.
+    is_polled: AtomicBool,
+    /// When a stick direction last fired, freezing the mouse for `mouse_freeze_after_direction`.
+    last_direction_instant: Mutex<Option<Instant>>,
+    /// The `macro` remaps played by the controller, by input name.
+    running_macros: tokio::sync::Mutex<HashMap<String, RunningMacro>>,
+}
+
+impl Pad {
+    fn new(id: PadId) -> Self {
+        Self {
+            id,
+            name: Mutex::new(None),
+            is_alternative_active: AtomicBool::new(false),
+            is_chord_active: AtomicBool::new(false),
+            active_layers: Mutex::new(Vec::new()),
+            left_stick_coord: Coordinate::new(),
+            right_stick_coord: Coordinate::new(),
+            left_stick_peak: Coordinate::new(),
+            left_trigger_pull: AtomicF32::new(),
+            right_trigger_pull: AtomicF32::new(),
+            right_stick_peak: Coordinate::new(),
+            gyro_delta: Delta::new(),
+            left_stick_mouse_remainder: Coordinate::new(),
+            right_stick_mouse_remainder: Coordinate::new(),
+            left_stick_notify: tokio::sync::Notify::new(),
+            right_stick_notify: tokio::sync::Notify::new(),
+            held_inputs: Mutex::new(HashSet::new()),
+            suppressed_inputs: Mutex::new(HashSet::new()),
+            overridden_inputs: Mutex::new(HashSet::new()),
+            active_chords: Mutex::new(Vec::new()),
+            chord_inputs: Mutex::new(HashSet::new()),
+            latched_toggles: Mutex::new(HashMap::new()),
+            taps: Mutex::new(HashMap::new()),
+            disconnect_abort_handle: tokio::sync::Mutex::new(None),
+            debounced_releases: tokio::sync::Mutex::new(HashMap::new()),
+            repeat_abort_handles: tokio::sync::Mutex::new(HashMap::new()),
+            turbo_abort_handles: tokio::sync::Mutex::new(HashMap::new()),
+            is_reset: AtomicBool::new(false),
+            is_polled: AtomicBool::new(false),
+            last_direction_instant: Mutex::new(None),
+            running_macros: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the last known position of a stick.
+    fn stick_coord(&self, stick: Stick) -> &Coordinate {
+        match stick {
+            Stick::Left => &self.left_stick_coord,
+            Stick::Right => &self.right_stick_coord,
+        }
+    }
+
+    /// Returns the position of a stick farthest from the center since its last poll.
+    fn stick_peak(&self, stick: Stick) -> &Coordinate {
+        match stick {
+            Stick::Left => &self.left_stick_peak,
+            Stick::Right => &self.right_stick_peak,
+        }
+    }
+
+    /// Returns the sub-pixel movement a stick accumulated for the mouse.
+    fn stick_mouse_remainder(&self, stick: Stick) -> &Coordinate {
+        match stick {
+            Stick::Left => &self.left_stick_mouse_remainder,
+            Stick::Right => &self.right_stick_mouse_remainder,
+        }
+    }
+
+    /// Returns the notifier woken when a stick moves.
+    fn stick_notify(&self, stick: Stick) -> &tokio::sync::Notify {
+        match stick {
+            Stick::Left => &self.left_stick_notify,
+            Stick::Right => &self.right_stick_notify,
+        }
+    }
+
+    /// Returns how far an analog trigger is pulled.
+    fn trigger_pull(&self, trigger: Trigger) -> &AtomicF32 {
+        match trigger {
+            Trigger::LeftTrigger => &self.left_trigger_pull,
+            Trigger::RightTrigger => &self.right_trigger_pull,
+        }
+    }
+
+    /// Checks whether the alternative remap set is the one in use.
+    ///
+    /// Holding the activator or the chord switches away from the baseline set,
+    /// which is `main` unless the layers were swapped by a `swap_layers` remap.
+    fn is_alternative_active(&self) -> bool {
+        let is_activated =
+            self.is_alternative_active.load(Ordering::Relaxed) || self.is_chord_active.load(Ordering::Relaxed);
+        is_activated != ARE_LAYERS_SWAPPED.load(Ordering::Relaxed)
+    }
+
+    /// Returns the name of the layer remapping the inputs first: the last activated
+    /// named layer, or else `alt` or `main`.
+    fn active_layer(&self) -> String {
+        match self.active_layers.lock().unwrap().last() {
+            Some(layer) => layer.clone(),
+            None if self.is_alternative_active() => "alt".to_string(),
+            None => "main".to_string(),
+        }
+    }
+}
+
+static ARE_LAYERS_SWAPPED: AtomicBool = AtomicBool::new(false);
+static IS_ON_BATTERY: AtomicBool = AtomicBool::new(false);
+static IS_DRY_RUN: AtomicBool = AtomicBool::new(false);
+static HAS_LOGGED_CLAMPING: AtomicBool = AtomicBool::new(false);
+static HAS_LOGGED_DISPLAY_ERROR: AtomicBool = AtomicBool::new(false);
+
+static CONFIG: OnceLock<RwLock<&'static Config>> = OnceLock::new();
+static ACTIVE_CONFIG_PATH: OnceLock<Mutex<PathBuf>> = OnceLock::new();
+static INPUT_SENDER: OnceLock<InputSender> = OnceLock::new();
+static TYPE_CHARS_ABORT_HANDLE: OnceLock<tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>> =
+    OnceLock::new();
+static KEY_DOWNS: OnceLock<Mutex<HashSet<enigo::Key>>> = OnceLock::new();
+static PADS: OnceLock<Mutex<HashMap<PadId, &'static Pad>>> = OnceLock::new();
+
+fn config_path() -> PathBuf {
+    std::env::current_exe().unwrap().with_extension("toml")
+}
+
+/// Returns the path of the config file in use, the default one unless a profile was switched to.
+fn active_config_path() -> PathBuf {
+    ACTIVE_CONFIG_PATH
+        .get_or_init(|| Mutex::new(config_path()))
+        .lock()
+        .unwrap()
+        .clone()
+}
+
+/// Writes the commented config template to a file.
+///
+/// # Returns
+///
+/// An error if the file already exists and writing is not forced, or if it cannot be written.
+fn generate_config(path: &Path, is_forced: bool) -> Result<(), String> {
+    let write_error = |err: std::io::Error| match err.kind() {
+        std::io::ErrorKind::AlreadyExists => format!("{} already exists, pass --force to overwrite it", path.display()),
+        _ => format!("Unable to write {}: {err}", path.display()),
+    };
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!is_forced)
+        .open(path)
+        .and_then(|mut file| file.write_all(config::CONFIG_TEMPLATE.as_bytes()))
+        .map_err(write_error)
+}
+
+/// Parses and validates the content of a config file.
+///
+/// A file written for an older layout is migrated first, and unknown options
+/// are reported but ignored.
+fn parse_config(config_str: &str) -> Result<Config, String> {
+    let parse_error = |err| format!("Unable to parse the config file: {err}");
+    let mut table = toml::from_str::<toml::Table>(config_str).map_err(parse_error)?;
+    let is_migrated = config::migrate(&mut table).map_err(|err| format!("Unable to migrate the config file: {err}"))?;
+
+    let unknown_options = config::unknown_options(&table);
+    if !unknown_options.is_empty() {
+        log::warn!("Unknown options in the config file are ignored: {}", unknown_options.join(", "));
+    }
+
+    // Parsing the text again rather than the table leaves the positions in its errors
+    let config = if is_migrated {
+        toml::Value::Table(table).try_into::<Config>()
+    } else {
+        toml::from_str::<Config>(config_str)
+    };
+    config
+        .map_err(parse_error)?
+        .resolve_aliases()
+        .and_then(Config::check_error)
+        .map_err(|errors| format!("Invalid config file: {}", errors.join("; ")))
+}
+
+/// Returns the current config.
+///
+/// Configs are leaked so that references to them stay valid after a reload,
+/// e.g. in a repeat task. A reload happens when the file is saved, so the
+/// leaked memory is negligible.
+fn get_config() -> &'static Config {
+    *CONFIG
+        .get_or_init(|| {
+            let config_str = std::fs::read_to_string(config_path()).unwrap_or_default();
+            let config = parse_config(&config_str).unwrap_or_else(|err| {
+                alert::show_error(&format!("{err}\n\nThe default configuration is used instead."));
+                parse_config("").unwrap()
+            });
+            RwLock::new(Box::leak(Box::new(config)))
+        })
+        .read()
+        .unwrap()
+}
+
+/// Replaces the current config, keeping it if the new one is invalid.
+///
+/// Held inputs are released first, so that their release does not go through
+/// a different remap than their press.
+///
+/// # Returns
+///
+/// Whether the config was replaced.
+async fn reload_config(config_str: &str) -> bool {
+    match parse_config(config_str) {
+        Ok(config) => {
+            reset_all_controller_states().await;
+            // Initializes the lock if the first config was never loaded
+            get_config();
+            *CONFIG.get().unwrap().write().unwrap() = Box::leak(Box::new(config));
+            // A reload is also the usual way to follow a display change at once
+            get_input_sender().refresh_main_display();
+            true
+        }
+        Err(err) => {
+            alert::show_error(&format!("{err}\n\nThe previous configuration is kept."));
+            false
+        }
+    }
+}
+
+/// Replaces the current config by a profile file, whose modifications are then watched instead.
+///
+/// A relative path is resolved from the directory of the default config file.
+/// The future is boxed since reloading fires remaps, which can switch profiles.
+fn switch_profile(profile: &'static str) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(async move {
+        let path = match config_path().parent() {
+            Some(dir) => dir.join(profile),
+            None => PathBuf::from(profile),
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(config_str) => {
+                if reload_config(&config_str).await {
+                    *ACTIVE_CONFIG_PATH.get_or_init(|| Mutex::new(config_path())).lock().unwrap() = path.clone();
+                    log::info!("Switched to the profile {}", path.display());
+                }
+            }
+            Err(err) => alert::show_error(&format!(
+                "Unable to read the profile {}: {err}\n\nThe previous configuration is kept.",
+                path.display()
+            )),
+        }
+    })
+}
+
+/// How often the config file is checked for modifications.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reloads the config file in use whenever it is modified.
+async fn watch_config() {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let mut last_path = active_config_path();
+    let mut last_modified = modified(&last_path);
+
+    loop {
+        tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+
+        // A profile switched to was just loaded, only its next modifications count
+        let path = active_config_path();
+        let curr_modified = modified(&path);
+        if path != last_path {
+            last_path = path;
+            last_modified = curr_modified;
+            continue;
+        }
+
+        if curr_modified != last_modified {
+            last_modified = curr_modified;
+
+            let config_str = std::fs::read_to_string(&path).unwrap_or_default();
+            if reload_config(&config_str).await {
+                log::info!("Reloaded the config file");
+            }
+        }
+    }
+}
+
+/// Returns the sender of the inputs of the button remaps and the stick loops.
+///
+/// A single worker owns the backend and injects the inputs in the order they are
+/// sent, so no remap waits for another one, not even across a macro delay.
+fn get_input_sender() -> &'static InputSender {
+    INPUT_SENDER.get_or_init(|| {
+        let is_dry_run = IS_DRY_RUN.load(Ordering::Relaxed);
+        InputSender::spawn(backend::new_backend(is_dry_run, &get_config().enigo_settings()))
+    })
+}
+
+/// Returns the keys pressed by `key_down` remaps and not released yet.
+fn get_key_downs() -> &'static Mutex<HashSet<enigo::Key>> {
+    KEY_DOWNS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn get_type_chars_abort_handle() -> &'static tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>
+{
+    TYPE_CHARS_ABORT_HANDLE.get_or_init(|| tokio::sync::Mutex::new(None))
+}
+
+/// Returns the state of a controller, created the first time it is seen.
+///
+/// Pads are never removed, so that a reconnecting controller finds its state
+/// back; there are only as many as controllers ever connected.
+fn get_pad(id: PadId) -> &'static Pad {
+    PADS.get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap()
+        .entry(id)
+        .or_insert_with(|| Box::leak(Box::new(Pad::new(id))))
+}
+
+/// Returns the state of every controller seen so far.
+fn get_pads() -> Vec<&'static Pad> {
+    PADS.get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap()
+        .values()
+        .copied()
+        .collect()
+}
+
+/// Releases every held button and resets the state of a controller.
+///
+/// Stick directions are not released here: resetting the coordinates makes the
+/// stick loops release them on their next tick.
+async fn reset_controller_state(pad: &'static Pad) {
+    let activator = get_config().alternative_activator.as_deref();
+    let held_buttons = pad
+        .held_inputs
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|input_name| names::is_button_input_name(input_name))
+        .filter(|input_name| Some(input_name.as_str()) != activator)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for handle in std::mem::take(&mut *pad.debounced_releases.lock().await).into_values() {
+        handle.abort();
+    }
+    for input_name in held_buttons {
+        update_input(pad, &input_name, false).await;
+    }
+
+    for tap in std::mem::take(&mut *pad.taps.lock().unwrap()).into_values() {
+        if let TapState::Pending { handle, .. } = tap {
+            handle.abort();
+        }
+    }
+
+    let latched_toggles = std::mem::take(&mut *pad.latched_toggles.lock().unwrap());
+    for keys in latched_toggles.into_values() {
+        get_input_sender().send(keys.iter().rev().map(|key| InputCommand::Key(*key, Direction::Release)));
+    }
+
+    let running_macros = std::mem::take(&mut *pad.running_macros.lock().await);
+    for running in running_macros.into_values() {
+        stop_macro(running).await;
+    }
+
+    // Toggled sets are a choice of the user rather than held buttons, so they are kept
+    if get_config().alternative_activator_mode == ActivatorMode::Hold {
+        pad.is_alternative_active.store(false, Ordering::Relaxed);
+    }
+    pad.is_chord_active.store(false, Ordering::Relaxed);
+    pad.active_layers
+        .lock()
+        .unwrap()
+        .retain(|layer| get_config().layer_activator_mode(layer) == ActivatorMode::Toggle);
+    pad.held_inputs.lock().unwrap().clear();
+    pad.suppressed_inputs.lock().unwrap().clear();
+    pad.overridden_inputs.lock().unwrap().clear();
+    pad.active_chords.lock().unwrap().clear();
+    pad.chord_inputs.lock().unwrap().clear();
+    pad.left_stick_coord.reset();
+    pad.right_stick_coord.reset();
+    pad.gyro_delta.reset();
+    pad.left_trigger_pull.reset();
+    pad.right_trigger_pull.reset();
+    pad.left_stick_mouse_remainder.reset();
+    pad.right_stick_mouse_remainder.reset();
+}
+
+/// Releases every held button and resets the state of all controllers.
+async fn reset_all_controller_states() {
+    for pad in get_pads() {
+        reset_controller_state(pad).await;
+    }
+
+    // The keys of `key_down` remaps are held by no input, so they are released with every controller
+    let key_downs = std::mem::take(&mut *get_key_downs().lock().unwrap());
+    if !key_downs.is_empty() {
+        get_input_sender().send(key_downs.into_iter().map(|key| InputCommand::Key(key, Direction::Release)));
+    }
+}
+
+/// Handles a controller disconnect by scheduling a state reset.
+///
+/// The reset is delayed by `debounce`, so a controller that reconnects within
+/// that window keeps its layer and held inputs.
+async fn handle_disconnected(pad: &'static Pad, debounce: Duration) {
+    let mut abort_handle_lock = pad.disconnect_abort_handle.lock().await;
+
+    if let Some(handle) = abort_handle_lock.take() {
+        handle.abort();
+    }
+
+    if debounce.is_zero() {
+        reset_controller_state(pad).await;
+        pad.is_reset.store(true, Ordering::Relaxed);
+    } else {
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(debounce).await;
+            reset_controller_state(pad).await;
+            pad.is_reset.store(true, Ordering::Relaxed);
+        });
+        *abort_handle_lock = Some(handle);
+    }
+}
+
+/// Handles a controller connection by canceling any pending state reset.
+///
+/// A controller reconnecting after its state was reset also leaves its toggled
+/// sets, which are only kept while it is away. Its own remap sets, if any,
+/// are selected again by the name it reports.
+async fn handle_connected(pad: &Pad, name: Option<String>) {
+    let is_reset_pending = match pad.disconnect_abort_handle.lock().await.take() {
+        Some(handle) => {
+            handle.abort();
+            !handle.is_finished()
+        }
+        None => false,
+    };
+
+    if name.is_some() {
+        *pad.name.lock().unwrap() = name;
+    }
+    let name = pad.name.lock().unwrap().clone().unwrap_or_else(|| "Controller".to_string());
+
+    if pad.is_reset.swap(false, Ordering::Relaxed) {
+        pad.is_alternative_active.store(false, Ordering::Relaxed);
+        pad.active_layers.lock().unwrap().clear();
+        log::info!("{name} reconnected");
+    } else if is_reset_pending {
+        log::info!("{name} reconnected, keeping its state");
+    } else {
+        log::info!("{name} connected");
+    }
+}
+
+/// Checks whether the mouse movement of a controller is frozen because one of its stick directions just fired.
+fn is_mouse_frozen(pad: &Pad) -> bool {
+    if let Some(freeze) = get_config().mouse_freeze_after_direction
+        && let Some(instant) = *pad.last_direction_instant.lock().unwrap()
+    {
+        instant.elapsed() < freeze
+    } else {
+        false
+    }
+}
+
+/// Decides whether a binding is skipped because of its `unless` or `when_window` condition.
+///
+/// The condition is only checked on press-down. The release of a suppressed
+/// press is skipped as well, so no key is ever released without being pressed.
+fn is_binding_suppressed(pad: &Pad, input_name: &str, binding: &Binding, is_press_down: bool) -> bool {
+    let mut suppressed_inputs = pad.suppressed_inputs.lock().unwrap();
+
+    if is_press_down {
+        let is_suppressed = binding.is_suppressed(&pad.held_inputs.lock().unwrap())
+            || binding.when_window.as_ref().is_some_and(|matcher| !window::is_focused(matcher));
+        if is_suppressed {
+            suppressed_inputs.insert(input_name.to_string());
+        }
+        is_suppressed
+    } else {
+        suppressed_inputs.remove(input_name)
+    }
+}
+
+/// Spawns a command line of a remap without waiting for it, logging when it cannot be started.
+fn run_command(cmdline: &str, command: &CommandLine) {
+    if IS_DRY_RUN.load(Ordering::Relaxed) {
+        log::info!("Run {cmdline:?}");
+    } else if let Some(components) = shlex::split(cmdline)
+        && !components.is_empty()
+    {
+        let mut process = std::process::Command::new(&components[0]);
+        process.args(&components[1..]).envs(&command.env);
+        if let Some(cwd) = &command.cwd {
+            process.current_dir(cwd);
+        }
+
+        if let Err(err) = process.spawn() {
+            log::error!("Failed to run {cmdline:?}: {err}");
+        }
+    }
+}
+
+/// A `macro` remap being played.
+struct RunningMacro {
+    handle: tokio::task::JoinHandle<()>,
+    steps: &'static [MacroStep],
+    /// Number of steps already played.
+    progress: Arc<AtomicUsize>,
+}
+
+/// Returns the keys held down after playing macro steps, in the order they were pressed.
+fn held_macro_keys(steps: &[MacroStep]) -> Vec<enigo::Key> {
+    let mut keys = Vec::new();
+
+    for step in steps {
+        match step {
+            MacroStep::Press(key) if !keys.contains(key) => keys.push(*key),
+            MacroStep::Release(key) => keys.retain(|held| held != key),
+            _ => (),
+        }
+    }
+    keys
+}
+
+/// Stops a `macro` remap and releases the keys it holds down.
+async fn stop_macro(running: RunningMacro) {
+    running.handle.abort();
+
+    // A step is counted as soon as it is sent, so the count is exact once the macro is aborted
+    let played = running.progress.load(Ordering::Relaxed);
+    let held_keys = held_macro_keys(&running.steps[..played]);
+    get_input_sender().send(held_keys.into_iter().rev().map(|key| InputCommand::Key(key, Direction::Release)));
+}
+
+/// Plays the `macro` remap of an input in the background.
+///
+/// A press while the macro of the same input is still playing is ignored, unless
+/// `macro_cancel_on_press` is set: the playing macro then stops, the keys it
+/// holds down are released, and the new one starts. The macros of other inputs
+/// and controllers play on.
+async fn fire_macro(pad: &Pad, input_name: &str, steps: &'static [MacroStep]) {
+    let mut running_macros = pad.running_macros.lock().await;
+
+    if let Some(running) = running_macros.remove(input_name)
+        && !running.handle.is_finished()
+    {
+        if !get_config().macro_cancel_on_press {
+            running_macros.insert(input_name.to_string(), running);
+            return;
+        }
+        stop_macro(running).await;
+    }
+
+    let progress = Arc::new(AtomicUsize::new(0));
+    let handle = tokio::spawn({
+        let progress = progress.clone();
+        async move {
+            for step in steps {
+                match step {
+                    MacroStep::Press(key) | MacroStep::Release(key) => {
+                        let direction = if matches!(step, MacroStep::Press(_)) {
+                            Direction::Press
+                        } else {
+                            Direction::Release
+                        };
+                        get_input_sender().send([InputCommand::Key(*key, direction)]);
+                        progress.fetch_add(1, Ordering::Relaxed);
+                    }
+                    MacroStep::Delay(delay) => {
+                        tokio::time::sleep(*delay).await;
+                        progress.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+    });
+    running_macros.insert(input_name.to_string(), RunningMacro { handle, steps, progress });
+}
+
+/// Returns how many times per second an `analog` remap fires.
+///
+/// # Arguments
+///
+/// * `pull` - How far the trigger is pulled, or `None` for a button, which counts as fully pulled.
+/// * `threshold` - The pull at which the trigger is pressed, where `min_rate` applies.
+/// * `curve` - The exponent applied to the pull past the threshold.
+fn analog_rate(pull: Option<f32>, threshold: f32, min_rate: f32, max_rate: f32, curve: f32) -> f32 {
+    let progress = match pull {
+        Some(pull) if threshold < 1. => ((pull - threshold) / (1. - threshold)).clamp(0., 1.),
+        _ => 1.,
+    };
+    min_rate + (max_rate - min_rate) * progress.powf(curve)
+}
+
+/// Presses and releases a remap repeatedly while its input is held, waiting
+/// `interval()` after each time.
+async fn fire_turbo(
+    pad: &'static Pad,
+    input_name: &str,
+    inner: &'static Remap,
+    interval: impl Fn() -> Duration + Send + 'static,
+    is_press_down: bool,
+) {
+    let mut abort_handles = pad.turbo_abort_handles.lock().await;
+
+    if let Some(handle) = abort_handles.remove(input_name) {
+        handle.abort();
+    }
+
+    if is_press_down {
+        let task_input_name = input_name.to_string();
+        let handle = tokio::spawn(async move {
+            loop {
+                fire_nested_remap(pad, &task_input_name, inner, true).await;
+                fire_nested_remap(pad, &task_input_name, inner, false).await;
+                tokio::time::sleep(interval()).await;
+            }
+        });
+        abort_handles.insert(input_name.to_string(), handle);
+    }
+}
+
+/// Boxes `fire_remap` for remaps made of other remaps, which makes it recursive.
+fn fire_nested_remap<'a>(
+    pad: &'static Pad,
+    input_name: &'a str,
+    remap: &'static Remap,
+    is_press_down: bool,
+) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+    Box::pin(fire_remap(pad, input_name, remap, is_press_down))
+}
+
+/// Presses or releases a `double_tap` remap.
+///
+/// A first press waits for `double_tap_window`: a second press within it fires
+/// `double`, otherwise `single` fires once the window is over, as a tap if the
+/// input was released meanwhile, or held until the release otherwise.
+async fn fire_double_tap(
+    pad: &'static Pad,
+    input_name: &str,
+    single: &'static Remap,
+    double: &'static Remap,
+    is_press_down: bool,
+) {
+    let tap = pad.taps.lock().unwrap().remove(input_name);
+
+    match (tap, is_press_down) {
+        (Some(TapState::Pending { handle, .. }), true) => {
+            handle.abort();
+            pad.taps
+                .lock()
+                .unwrap()
+                .insert(input_name.to_string(), TapState::Firing(double));
+            fire_nested_remap(pad, input_name, double, true).await;
+        }
+        (_, true) => {
+            let task_input_name = input_name.to_string();
+            let handle = tokio::spawn(async move {
+                tokio::time::sleep(get_config().double_tap_window).await;
+
+                let is_released = {
+                    let mut taps = pad.taps.lock().unwrap();
+                    match taps.get(&task_input_name) {
+                        Some(TapState::Pending { is_released, .. }) => {
+                            let is_released = *is_released;
+                            taps.insert(task_input_name.clone(), TapState::Firing(single));
+                            is_released
+                        }
+                        _ => return,
+                    }
+                };
+
+                fire_nested_remap(pad, &task_input_name, single, true).await;
+                if is_released {
+                    pad.taps.lock().unwrap().remove(&task_input_name);
+                    fire_nested_remap(pad, &task_input_name, single, false).await;
+                }
+            });
+            pad.taps.lock().unwrap().insert(
+                input_name.to_string(),
+                TapState::Pending {
+                    handle,
+                    is_released: false,
+                },
+            );
+        }
+        (Some(TapState::Pending { handle, .. }), false) => {
+            pad.taps.lock().unwrap().insert(
+                input_name.to_string(),
+                TapState::Pending {
+                    handle,
+                    is_released: true,
+                },
+            );
+        }
+        (Some(TapState::Firing(remap)), false) => {
+            fire_nested_remap(pad, input_name, remap, false).await;
+        }
+        (None, false) => (),
+    }
+}
+
+/// Presses or releases a `hold_tap` remap.
+///
+/// A press waits for `hold_duration`: releasing the input before fires `tap`,
+/// otherwise `hold` is pressed once the wait is over and released with the input.
+async fn fire_hold_tap(
+    pad: &'static Pad,
+    input_name: &str,
+    tap: &'static Remap,
+    hold: &'static Remap,
+    hold_duration: Duration,
+    is_press_down: bool,
+) {
+    let state = pad.taps.lock().unwrap().remove(input_name);
+
+    match (state, is_press_down) {
+        (state, true) => {
+            if let Some(TapState::Pending { handle, .. }) = state {
+                handle.abort();
+            }
+
+            let task_input_name = input_name.to_string();
+            let handle = tokio::spawn(async move {
+                tokio::time::sleep(hold_duration).await;
+
+                {
+                    let mut taps = pad.taps.lock().unwrap();
+                    if !matches!(taps.get(&task_input_name), Some(TapState::Pending { .. })) {
+                        return;
+                    }
+                    taps.insert(task_input_name.clone(), TapState::Firing(hold));
+                }
+                fire_nested_remap(pad, &task_input_name, hold, true).await;
+            });
+            pad.taps.lock().unwrap().insert(
+                input_name.to_string(),
+                TapState::Pending {
+                    handle,
+                    is_released: false,
+                },
+            );
+        }
+        (Some(TapState::Pending { handle, .. }), false) => {
+            handle.abort();
+            fire_nested_remap(pad, input_name, tap, true).await;
+            fire_nested_remap(pad, input_name, tap, false).await;
+        }
+        (Some(TapState::Firing(remap)), false) => {
+            fire_nested_remap(pad, input_name, remap, false).await;
+        }
+        (None, false) => (),
+    }
+}
+
+/// Presses or releases a `delayed` remap.
+///
+/// A press waits for `delay`: releasing the input before cancels the remap,
+/// otherwise `inner` is pressed once the wait is over and released with the input.
+async fn fire_delayed(
+    pad: &'static Pad,
+    input_name: &str,
+    inner: &'static Remap,
+    delay: Duration,
+    is_press_down: bool,
+) {
+    let state = pad.taps.lock().unwrap().remove(input_name);
+
+    match (state, is_press_down) {
+        (state, true) => {
+            if let Some(TapState::Pending { handle, .. }) = state {
+                handle.abort();
+            }
+
+            let task_input_name = input_name.to_string();
+            let handle = tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+
+                {
+                    let mut taps = pad.taps.lock().unwrap();
+                    if !matches!(taps.get(&task_input_name), Some(TapState::Pending { .. })) {
+                        return;
+                    }
+                    taps.insert(task_input_name.clone(), TapState::Firing(inner));
+                }
+                fire_nested_remap(pad, &task_input_name, inner, true).await;
+            });
+            pad.taps.lock().unwrap().insert(
+                input_name.to_string(),
+                TapState::Pending {
+                    handle,
+                    is_released: false,
+                },
+            );
+        }
+        (Some(TapState::Pending { handle, .. }), false) => handle.abort(),
+        (Some(TapState::Firing(remap)), false) => {
+            fire_nested_remap(pad, input_name, remap, false).await;
+        }
+        (None, false) => (),
+    }
+}
+
+/// Performs the action of a remap for a press or release of its input.
+async fn fire_remap(pad: &'static Pad, input_name: &str, remap: &'static Remap, is_press_down: bool) {
+    match remap {
+        Remap::Seq(seq) => {
+            if is_press_down {
+                let presses = seq.iter().map(|key| InputCommand::Key(*key, Direction::Press));
+                let releases = seq.iter().rev().map(|key| InputCommand::Key(*key, Direction::Release));
+                get_input_sender().send(presses.chain(releases));
+            }
+        }
+        Remap::Sync(seq) => {
+            if is_press_down {
+                get_input_sender().send(seq.iter().map(|key| InputCommand::Key(*key, Direction::Press)));
+            } else {
+                get_input_sender().send(seq.iter().rev().map(|key| InputCommand::Key(*key, Direction::Release)));
+            }
+        }
+        Remap::Toggle(keys) => {
+            if is_press_down {
+                let was_latched = pad.latched_toggles.lock().unwrap().remove(input_name).is_some();
+
+                if was_latched {
+                    get_input_sender().send(keys.iter().rev().map(|key| InputCommand::Key(*key, Direction::Release)));
+                } else {
+                    get_input_sender().send(keys.iter().map(|key| InputCommand::Key(*key, Direction::Press)));
+                    pad.latched_toggles.lock().unwrap().insert(input_name.to_string(), keys);
+                }
+            }
+        }
+        Remap::KeyDown(key) => {
+            if is_press_down {
+                get_input_sender().send([InputCommand::Key(*key, Direction::Press)]);
+                get_key_downs().lock().unwrap().insert(*key);
+            }
+        }
+        Remap::KeyUp(key) => {
+            if is_press_down {
+                get_input_sender().send([InputCommand::Key(*key, Direction::Release)]);
+                get_key_downs().lock().unwrap().remove(key);
+            }
+        }
+        Remap::Macro(steps) => {
+            if is_press_down {
+                fire_macro(pad, input_name, steps).await;
+            }
+        }
+        Remap::Repeat(repeat) => {
+            let mut abort_handles = pad.repeat_abort_handles.lock().await;
+
+            if let Some(handle) = abort_handles.remove(input_name) {
+                handle.abort();
+            }
+
+            if is_press_down {
+                get_input_sender().send([InputCommand::Key(repeat.key, Direction::Click)]);
+
+                let handle = tokio::spawn(async move {
+                    let initial_delay = repeat.initial_delay.unwrap_or(get_config().key_repeat_initial_delay);
+                    tokio::time::sleep(initial_delay).await;
+
+                    for repeats in 0.. {
+                        get_input_sender().send([InputCommand::Key(repeat.key, Direction::Click)]);
+
+                        tokio::time::sleep(repeat.delay_after(repeats, get_config().key_repeat_sub_delay)).await;
+                    }
+                });
+                abort_handles.insert(input_name.to_string(), handle);
+            }
+        }
+        Remap::Mouse(button) => {
+            let direction = if is_press_down {
+                Direction::Press
+            } else {
+                Direction::Release
+            };
+            get_input_sender().send([InputCommand::Button(*button, direction)]);
+        }
+        Remap::MouseClick(button) => {
+            if is_press_down {
+                get_input_sender().send([InputCommand::Button(*button, Direction::Click)]);
+            }
+        }
+        Remap::Nudge { dx, dy, repeat } => {
+            let mut abort_handles = pad.repeat_abort_handles.lock().await;
+
+            if let Some(handle) = abort_handles.remove(input_name) {
+                handle.abort();
+            }
+
+            if is_press_down {
+                get_input_sender().send([InputCommand::MoveMouse(*dx, *dy, enigo::Coordinate::Rel)]);
+
+                if *repeat {
+                    let handle = tokio::spawn(async move {
+                        tokio::time::sleep(get_config().key_repeat_initial_delay).await;
+
+                        loop {
+                            get_input_sender().send([InputCommand::MoveMouse(*dx, *dy, enigo::Coordinate::Rel)]);
+                            tokio::time::sleep(get_config().key_repeat_sub_delay).await;
+                        }
+                    });
+                    abort_handles.insert(input_name.to_string(), handle);
+                }
+            }
+        }
+        Remap::MoveTo { x, y } => {
+            if is_press_down {
+                get_input_sender().send([InputCommand::MoveMouse(*x, *y, enigo::Coordinate::Abs)]);
+            }
+        }
+        Remap::Scroll { x, y } => {
+            if is_press_down {
+                get_input_sender().send(scroll_commands(*x, *y));
+            }
+        }
+        Remap::ScrollRepeat { axis, amount, interval } => {
+            let mut abort_handles = pad.repeat_abort_handles.lock().await;
+
+            if let Some(handle) = abort_handles.remove(input_name) {
+                handle.abort();
+            }
+
+            if is_press_down {
+                let handle = tokio::spawn(async move {
+                    let mut scrolls = 0;
+                    // The fraction of a wheel tick not scrolled yet
+                    let mut remainder = 0.;
+
+                    loop {
+                        let speed = stick::ramp_speed(
+                            get_config().mouse_acceleration_curve,
+                            get_config().scroll_initial_speed,
+                            get_config().scroll_max_speed,
+                            scrolls,
+                            get_config().scroll_ticks_to_reach_max_speed,
+                        );
+                        let (ticks, new_remainder) = stick::take_whole_pixels(*amount as f32 * speed, remainder);
+                        remainder = new_remainder;
+
+                        if ticks != 0 {
+                            get_input_sender().send([InputCommand::Scroll(ticks, *axis)]);
+                        }
+                        scrolls = scrolls.saturating_add(1);
+                        tokio::time::sleep(*interval).await;
+                    }
+                });
+                abort_handles.insert(input_name.to_string(), handle);
+            }
+        }
+        Remap::Turbo { inner, interval } => {
+            fire_turbo(pad, input_name, inner, || *interval, is_press_down).await;
+        }
+        Remap::Analog {
+            inner,
+            min_rate,
+            max_rate,
+            curve,
+        } => {
+            let trigger = [Trigger::LeftTrigger, Trigger::RightTrigger]
+                .into_iter()
+                .find(|trigger| trigger.input_name() == input_name);
+            let interval = move || {
+                let pull = trigger.map(|trigger| pad.trigger_pull(trigger).load());
+                let threshold = get_config().trigger_activation_threshold.unwrap_or(0.);
+                Duration::from_secs_f32(1. / analog_rate(pull, threshold, *min_rate, *max_rate, *curve))
+            };
+            fire_turbo(pad, input_name, inner, interval, is_press_down).await;
+        }
+        Remap::Command(command) => {
+            if let Some(cmdline) = command.cmdline(is_press_down) {
+                run_command(cmdline, command);
+            }
+        }
+        Remap::TypeChars(text) => {
+            if is_press_down {
+                let mut abort_handle_lock = get_type_chars_abort_handle().lock().await;
+
+                // Pressing again while still typing cancels the text instead
+                if let Some(handle) = abort_handle_lock.take()
+                    && !handle.is_finished()
+                {
+                    handle.abort();
+                    return;
+                }
+
+                let handle = tokio::spawn(async move {
+                    let mut buffer = [0; 4];
+
+                    for char in text.chars() {
+                        get_input_sender().send([InputCommand::Text(char.encode_utf8(&mut buffer).to_string())]);
+                        tokio::time::sleep(get_config().type_chars_delay).await;
+                    }
+                });
+                *abort_handle_lock = Some(handle);
+            }
+        }
+        Remap::Text(text) => {
+            if is_press_down {
+                get_input_sender().send([InputCommand::Text(text.clone())]);
+            }
+        }
+        Remap::SwapLayers => {
+            if is_press_down {
+                ARE_LAYERS_SWAPPED.fetch_xor(true, Ordering::Relaxed);
+                indicator::show_layer(get_config().layer_indicator, &pad.active_layer());
+            }
+        }
+        Remap::SwitchProfile(profile) => {
+            // Switching releases the held inputs, this one included, so it cannot wait here
+            if is_press_down {
+                tokio::spawn(switch_profile(profile));
+            }
+        }
+        Remap::Block => {}
+        Remap::DoubleTap { single, double } => {
+            fire_double_tap(pad, input_name, single, double, is_press_down).await;
+        }
+        Remap::HoldTap { tap, hold, hold_duration } => {
+            fire_hold_tap(pad, input_name, tap, hold, *hold_duration, is_press_down).await;
+        }
+        Remap::Delayed { inner, delay } => {
+            fire_delayed(pad, input_name, inner, *delay, is_press_down).await;
+        }
+    }
+}
+
+/// Selects the alternative set if every input of the `chord_activator` is held, or deselects it.
+fn update_chord_activator(pad: &Pad) {
+    let held_inputs = pad.held_inputs.lock().unwrap();
+    let is_chord_active = get_config()
+        .chord_activator
+        .iter()
+        .all(|input_name| held_inputs.contains(input_name));
+    drop(held_inputs);
+
+    let was_alternative_active = pad.is_alternative_active();
+    pad.is_chord_active.store(is_chord_active, Ordering::Relaxed);
+    if pad.is_alternative_active() != was_alternative_active {
+        indicator::show_layer(get_config().layer_indicator, &pad.active_layer());
+    }
+}
+
+/// Presses or releases an input, ignoring the bounces within `button_debounce`.
+async fn press_input(pad: &'static Pad, input_name: &str, is_press_down: bool) {
+    let debounce = get_config().button_debounce;
+    if debounce.is_zero() {
+        update_input(pad, input_name, is_press_down).await;
+        return;
+    }
+
+    // A release is delayed until the debounce passes, so that a press bouncing back
+    // within it can cancel both. The pending task holds the lock while releasing.
+    let mut debounced_releases = pad.debounced_releases.lock().await;
+    if is_press_down {
+        if let Some(handle) = debounced_releases.remove(input_name) {
+            handle.abort();
+            log::debug!("Ignored bounce of {input_name}");
+            return;
+        }
+        drop(debounced_releases);
+        update_input(pad, input_name, true).await;
+    } else {
+        let owned_input_name = input_name.to_string();
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(debounce).await;
+            let mut debounced_releases = pad.debounced_releases.lock().await;
+            debounced_releases.remove(&owned_input_name);
+            update_input(pad, &owned_input_name, false).await;
+        });
+        if let Some(handle) = debounced_releases.insert(input_name.to_string(), handle) {
+            handle.abort();
+        }
+    }
+}
+
+/// Updates the held inputs and fires the bindings of an input being pressed or released.
+async fn update_input(pad: &'static Pad, input_name: &str, is_press_down: bool) {
+    {
+        let mut held_inputs = pad.held_inputs.lock().unwrap();
+        if is_press_down {
+            held_inputs.insert(input_name.to_string());
+        } else {
+            held_inputs.remove(input_name);
+        }
+    }
+
+    // The inputs of a chord keep their own bindings. The chord changes the set after
+    // a press and before a release, so that both resolve the binding of the same set.
+    let is_chord_input = get_config().chord_activator.iter().any(|chord_input| chord_input == input_name);
+    if is_chord_input && !is_press_down {
+        update_chord_activator(pad);
+    }
+
+    if !fire_chord(pad, input_name, is_press_down).await {
+        fire_resolving_socd(pad, input_name, is_press_down).await;
+    }
+
+    if is_chord_input && is_press_down {
+        update_chord_activator(pad);
+    }
+}
+
+/// Returns the largest chord of the active set that pressing an input completes, if any.
+fn completed_chord(pad: &Pad, input_name: &str) -> Option<&'static str> {
+    if get_config().chord_names().is_empty() {
+        return None;
+    }
+    let held_inputs = pad.held_inputs.lock().unwrap().clone();
+    let name = pad.name.lock().unwrap().clone();
+    let active_layers = pad.active_layers.lock().unwrap().clone();
+
+    get_config()
+        .chord_names()
+        .iter()
+        .filter_map(|chord| Some((chord.as_str(), names::chord_inputs(chord)?)))
+        .filter(|(_, inputs)| inputs.contains(&input_name) && inputs.iter().all(|input| held_inputs.contains(*input)))
+        .filter(|(chord, _)| {
+            get_config()
+                .get_layered_binding(chord, &active_layers, pad.is_alternative_active(), name.as_deref())
+                .is_some()
+        })
+        .max_by_key(|(_, inputs)| inputs.len())
+        .map(|(chord, _)| chord)
+}
+
+/// Fires the binding of the chord an input completes or ends, if any.
+///
+/// Completing a chord releases the bindings of its other inputs. They are then
+/// skipped, like the binding of the input completing it, until each is released.
+///
+/// # Returns
+///
+/// Whether the input is part of a chord, so that its own binding is skipped.
+async fn fire_chord(pad: &'static Pad, input_name: &str, is_press_down: bool) -> bool {
+    if is_press_down {
+        let Some(chord) = completed_chord(pad, input_name) else {
+            return false;
+        };
+        let released_inputs = names::chord_inputs(chord)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|input| *input != input_name && pad.chord_inputs.lock().unwrap().insert(input.to_string()))
+            .collect::<Vec<_>>();
+        for input in released_inputs {
+            fire_resolving_socd(pad, input, false).await;
+        }
+
+        pad.chord_inputs.lock().unwrap().insert(input_name.to_string());
+        pad.active_chords.lock().unwrap().push(chord.to_string());
+        fire_binding(pad, chord, true).await;
+        true
+    } else {
+        if !pad.chord_inputs.lock().unwrap().remove(input_name) {
+            return false;
+        }
+
+        let ended_chords = {
+            let mut active_chords = pad.active_chords.lock().unwrap();
+            let (ended_chords, kept_chords) = std::mem::take(&mut *active_chords)
+                .into_iter()
+                .partition::<Vec<_>, _>(|chord| {
+                    names::chord_inputs(chord).is_some_and(|inputs| inputs.contains(&input_name))
+                });
+            *active_chords = kept_chords;
+            ended_chords
+        };
+        for chord in ended_chords {
+            fire_binding(pad, &chord, false).await;
+        }
+        true
+    }
+}
+
+/// Fires the binding of an input, unless it is overridden by its opposite direction.
+///
+/// Releasing one of two opposite directions fires the binding of the other one
+/// if it was overridden, whichever `socd_resolution` is used.
+async fn fire_resolving_socd(pad: &'static Pad, input_name: &str, is_press_down: bool) {
+    let socd_resolution = get_config().socd_resolution;
+    let Some(opposite) = names::opposite_input_name(input_name).filter(|_| socd_resolution != SocdResolution::None)
+    else {
+        fire_binding(pad, input_name, is_press_down).await;
+        return;
+    };
+    let is_opposite_held = pad.held_inputs.lock().unwrap().contains(opposite);
+
+    if is_press_down {
+        if is_opposite_held {
+            if socd_resolution != SocdResolution::FirstWins
+                && pad.overridden_inputs.lock().unwrap().insert(opposite.to_string())
+            {
+                fire_binding(pad, opposite, false).await;
+            }
+            if socd_resolution != SocdResolution::LastWins {
+                pad.overridden_inputs.lock().unwrap().insert(input_name.to_string());
+                return;
+            }
+        }
+        fire_binding(pad, input_name, true).await;
+    } else {
+        if !pad.overridden_inputs.lock().unwrap().remove(input_name) {
+            fire_binding(pad, input_name, false).await;
+        }
+        if is_opposite_held && pad.overridden_inputs.lock().unwrap().remove(opposite) {
+            fire_binding(pad, opposite, true).await;
+        }
+    }
+}
+
+/// Fires the binding of an input in the active set, or switches sets if it is an activator.
+async fn fire_binding(pad: &'static Pad, input_name: &str, is_press_down: bool) {
+    if get_config().alternative_activator.as_deref() == Some(input_name) {
+        let was_alternative_active = pad.is_alternative_active();
+        match get_config().alternative_activator_mode {
+            ActivatorMode::Hold => pad.is_alternative_active.store(is_press_down, Ordering::Relaxed),
+            ActivatorMode::Toggle => {
+                if is_press_down {
+                    pad.is_alternative_active.fetch_xor(true, Ordering::Relaxed);
+                }
+            }
+        }
+        if pad.is_alternative_active() != was_alternative_active {
+            indicator::show_layer(get_config().layer_indicator, &pad.active_layer());
+        }
+
+        if is_press_down && let Some(feedback) = &get_config().activator_feedback {
+            feedback::play(pad.id, feedback);
+        }
+        return;
+    }
+
+    if let Some(layer) = get_config().layer_activated_by(input_name) {
+        let mut active_layers = pad.active_layers.lock().unwrap();
+        let is_active = active_layers.iter().any(|active_layer| active_layer == layer);
+        let should_be_active = match get_config().layer_activator_mode(layer) {
+            ActivatorMode::Hold => is_press_down,
+            ActivatorMode::Toggle => is_active != is_press_down,
+        };
+
+        if should_be_active && !is_active {
+            active_layers.push(layer.to_string());
+        } else if !should_be_active && is_active {
+            active_layers.retain(|active_layer| active_layer != layer);
+        } else {
+            return;
+        }
+        drop(active_layers);
+
+        indicator::show_layer(get_config().layer_indicator, &pad.active_layer());
+        return;
+    }
+
+    // The set may have changed since the press, so whatever the binding is now, its repeats stop
+    if !is_press_down {
+        for abort_handles in [&pad.repeat_abort_handles, &pad.turbo_abort_handles] {
+            if let Some(handle) = abort_handles.lock().await.remove(input_name) {
+                handle.abort();
+            }
+        }
+    }
+
+    let name = pad.name.lock().unwrap().clone();
+    let active_layers = pad.active_layers.lock().unwrap().clone();
+    if let Some(binding) =
+        get_config().get_layered_binding(input_name, &active_layers, pad.is_alternative_active(), name.as_deref())
+    {
+        if is_binding_suppressed(pad, input_name, binding, is_press_down) {
+            return;
+        }
+
+        if IS_DRY_RUN.load(Ordering::Relaxed) {
+            let state = if is_press_down { "pressed" } else { "released" };
+            log::info!("{input_name} {state}: {:?}", binding.remap);
+        }
+
+        fire_remap(pad, input_name, &binding.remap, is_press_down).await;
+
+        if is_press_down && let Some(feedback) = &binding.feedback {
+            feedback::play(pad.id, feedback);
+        }
+    }
+}
+
+/// How often the power source is checked when battery settings are configured.
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Keeps `IS_ON_BATTERY` up to date with the power source of the device.
+async fn watch_power() {
+    loop {
+        // Checked on every iteration, as the config may be reloaded
+        if get_config().battery_poll_interval.is_some() || get_config().battery_mouse_speed.is_some() {
+            IS_ON_BATTERY.store(power::is_on_battery(), Ordering::Relaxed);
+        }
+        tokio::time::sleep(POWER_CHECK_INTERVAL).await;
+    }
+}
+
+/// How often the size of the main display is measured again, for the sticks in `'absolute'` mode.
+const DISPLAY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Keeps the size of the main display up to date, e.g. after a resolution change.
+async fn watch_display() {
+    loop {
+        tokio::time::sleep(DISPLAY_CHECK_INTERVAL).await;
+        get_input_sender().refresh_main_display();
+    }
+}
+
+/// How often the focused window is checked when `release_on_focus_change` is set.
+const FOCUS_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Releases every held button when the focused window changes, so that no key
+/// stays pressed in an application that lost the focus, e.g. on a desktop lock.
+async fn watch_focus() {
+    let mut last_window_id = window::foreground_window_id();
+
+    loop {
+        let window_id = window::foreground_window_id();
+
+        // Checked on every iteration, as the config may be reloaded
+        if window_id != last_window_id && get_config().release_on_focus_change {
+            log::info!("The focused window changed, releasing held buttons");
+            reset_all_controller_states().await;
+        }
+        last_window_id = window_id;
+        tokio::time::sleep(FOCUS_CHECK_INTERVAL).await;
+    }
+}
+
+/// Returns the poll interval of a stick, replaced by `battery_poll_interval` on battery.
+fn stick_poll_interval(interval: Duration) -> Duration {
+    match get_config().battery_poll_interval {
+        Some(battery_interval) if IS_ON_BATTERY.load(Ordering::Relaxed) => battery_interval,
+        _ => interval,
+    }
+}
+
+/// Returns the next poll interval of a stick moving the mouse, as adapted to its motion.
+///
+/// While the stick keeps moving, the interval is halved down to `min`; once it
+/// stays still, it doubles back up to `base`.
+fn adapt_poll_interval(current: Duration, base: Duration, min: Option<Duration>, is_moving: bool) -> Duration {
+    match min {
+        Some(min) if min < base && is_moving => (current / 2).max(min),
+        Some(min) if min < base => (current * 2).min(base),
+        _ => base,
+    }
+}
+
+/// Returns the stick settings of a controller, with its own ones if configured for its name.
+///
+/// The stick loops call it on every poll, so that they follow reloads and reconnects.
+fn pad_stick_settings(pad: &Pad) -> StickSettings {
+    get_config().stick_settings(pad.name.lock().unwrap().as_deref())
+}
+
+/// Returns the maximum mouse speed of the stick settings, replaced by `battery_mouse_speed` on battery.
+fn mouse_max_speed(settings: &StickSettings) -> f32 {
+    match get_config().battery_mouse_speed {
+        Some(battery_speed) if IS_ON_BATTERY.load(Ordering::Relaxed) => battery_speed,
+        _ => settings.mouse_max_speed,
+    }
+}
+
+/// Moves the mouse according to a stick position, for one poll.
+///
+/// # Arguments
+///
+/// * `pad` - The controller of the stick, with its position and sub-pixel movement carried over.
+/// * `stick` - The stick moving the mouse.
+/// * `settings` - The stick settings of the controller.
+/// * `gyro` - The controller turns to add to the movement, if the gyro moves the mouse with this stick.
+/// * `scale` - The time since the previous poll, relative to `left_stick_poll_interval`.
+/// * `ticks` - The number of previous polls during which the mouse moved.
+/// * `batch` - The pixels moved since the last movement, with the time polled since.
+///
+/// # Returns
+///
+/// The number of polls during which the stick moved the mouse, reset when it stops.
+fn move_mouse_with_stick(
+    pad: &Pad,
+    stick: Stick,
+    settings: &StickSettings,
+    gyro: Option<&Delta>,
+    scale: f32,
+    ticks: u32,
+    batch: &mut MouseBatch,
+) -> u32 {
+    let curr_mouse_speed = stick::ramp_speed(
+        get_config().mouse_acceleration_curve,
+        settings.mouse_initial_speed,
+        mouse_max_speed(settings),
+        ticks,
+        get_config().mouse_ticks_to_reach_max_speed,
+    );
+    let remainder = pad.stick_mouse_remainder(stick);
+    let (x, y) = pad.stick_coord(stick).load_pair();
+    let (dead_zone, shape) = (settings.left_stick_dead_zone, get_config().left_stick_dead_zone_shape);
+    let (x, y) = if get_config().left_stick_dead_zone_rescale {
+        stick::apply_dead_zone(x, y, dead_zone, shape)
+    } else {
+        stick::gate_dead_zone(x, y, dead_zone, shape)
+    };
+    let (x, y) = if get_config().left_stick_normalize_diagonal {
+        stick::normalize_diagonal(x, y)
+    } else {
+        (x, y)
+    };
+    let (x, y) = stick::apply_response_curve(x, y, get_config().left_stick_response_curve);
+    let mut delta_x = x * curr_mouse_speed * get_config().mouse_sensitivity_x * scale;
+    let mut delta_y = y * curr_mouse_speed * get_config().mouse_sensitivity_y * scale;
+    let is_stick_moving = delta_x != 0. || delta_y != 0.;
+
+    // The gyro has no acceleration ramp: its movement is added as is
+    if let Some(gyro) = gyro {
+        delta_x += gyro.x.take() * get_config().gyro_sensitivity;
+        delta_y += gyro.y.take() * get_config().gyro_sensitivity;
+    }
+
+    let is_moving = delta_x != 0. || delta_y != 0.;
+    let mut pixels = (0, 0);
+    if is_moving && !is_mouse_frozen(pad) {
+        pixels = match get_config().mouse_rounding {
+            MouseRounding::Accumulate => {
+                let (last_remainder_x, last_remainder_y) = remainder.load_pair();
+                let (pixels_x, remainder_x) = stick::take_whole_pixels(delta_x, last_remainder_x);
+                let (pixels_y, remainder_y) = stick::take_whole_pixels(delta_y, last_remainder_y);
+                remainder.store_pair(remainder_x, remainder_y);
+                (pixels_x, pixels_y)
+            }
+            rounding => (stick::round_pixels(delta_x, rounding), stick::round_pixels(delta_y, rounding)),
+        };
+    } else if gyro.is_none() {
+        // The gyro moves in small steps between polls, so its fractions are always kept
+        remainder.reset();
+    }
+
+    if let Some((pixels_x, pixels_y)) = batch.add(pixels, is_moving) {
+        // Sticks and the gyro report up as positive Y, while the screen Y grows downwards
+        let pixels_y = if get_config().mouse_invert_y { -pixels_y } else { pixels_y };
+        get_input_sender().send([InputCommand::MoveMouse(pixels_x, pixels_y, enigo::Coordinate::Rel)]);
+    }
+
+    if is_stick_moving { ticks.saturating_add(1) } else { 0 }
+}
+
+/// Scrolls the mouse wheel according to a stick position, for one poll.
+///
+/// # Arguments
+///
+/// * `coord` - The position of the stick scrolling.
+/// * `remainder` - The fractions of wheel ticks carried over from the previous polls.
+/// * `settings` - The stick settings of the controller.
+///
+/// # Returns
+///
+/// Whether the stick is out of its dead zone, and so keeps scrolling.
+fn scroll_with_stick(coord: &Coordinate, remainder: &Coordinate, settings: &StickSettings) -> bool {
+    let (x, y) = coord.load_pair();
+    let (x, y) = stick::apply_dead_zone(x, y, settings.right_stick_dead_zone, DeadZoneShape::Radial);
+    if x == 0. && y == 0. {
+        remainder.reset();
+        return false;
+    }
+
+    let speed = get_config().stick_scroll_speed;
+    let (last_remainder_x, last_remainder_y) = remainder.load_pair();
+    let (ticks_x, remainder_x) = stick::take_whole_pixels(x * speed, last_remainder_x);
+    // Pushing the stick up scrolls up, by negative ticks
+    let (ticks_y, remainder_y) = stick::take_whole_pixels(-y * speed, last_remainder_y);
+    remainder.store_pair(remainder_x, remainder_y);
+
+    get_input_sender().send(scroll_commands(ticks_x, ticks_y));
+    true
+}
+
+/// Returns the inputs scrolling horizontally and vertically by a number of wheel ticks.
+fn scroll_commands(ticks_x: i32, ticks_y: i32) -> impl Iterator<Item = InputCommand> {
+    let horizontal = (ticks_x != 0).then_some(InputCommand::Scroll(ticks_x, enigo::Axis::Horizontal));
+    let vertical = (ticks_y != 0).then_some(InputCommand::Scroll(ticks_y, enigo::Axis::Vertical));
+    horizontal.into_iter().chain(vertical)
+}
+
+/// Moves the mouse to the display position matching a stick position, if it changed.
+///
+/// # Arguments
+///
+/// * `pad` - The controller of the stick.
+/// * `coord` - The position of the stick moving the mouse.
+/// * `settings` - The stick settings of the controller.
+/// * `display_size` - The size of the main display, which the stick maps to.
+/// * `last_position` - The position the mouse was last moved to.
+///
+/// # Returns
+///
+/// The position the mouse is at, and whether it lags behind the stick because the mouse is frozen.
+fn move_mouse_to_stick(
+    pad: &Pad,
+    coord: &Coordinate,
+    settings: &StickSettings,
+    (width, height): (i32, i32),
+    last_position: Option<(i32, i32)>,
+) -> (Option<(i32, i32)>, bool) {
+    // The dead zone is scaled so that a full push still reaches the edge of the display
+    let (x, y) = coord.load_pair();
+    let (x, y) = stick::apply_dead_zone(x, y, settings.left_stick_dead_zone, DeadZoneShape::Scaled);
+    let position = stick::absolute_position(x, y, width, height);
+
+    if Some(position) == last_position {
+        (last_position, false)
+    } else if is_mouse_frozen(pad) {
+        (last_position, true)
+    } else {
+        get_input_sender().send([InputCommand::MoveMouse(position.0, position.1, enigo::Coordinate::Abs)]);
+        (Some(position), false)
+    }
+}
+
+/// Returns the size of the main display, or `None` if the backend cannot tell it.
+///
+/// enigo's Wayland backend has no way to get it, so the error is only logged the first time,
+/// and the sticks in `'absolute'` mode move the mouse relatively instead.
+fn main_display_size() -> Option<(i32, i32)> {
+    match get_input_sender().main_display() {
+        Ok(size) => Some(size),
+        Err(err) => {
+            if !HAS_LOGGED_DISPLAY_ERROR.swap(true, Ordering::Relaxed) {
+                log::warn!("Failed to get the display size ({err}), absolute sticks move the mouse relatively");
+            }
+            None
+        }
+    }
+}
+
+/// Returns the stick whose loop moves the mouse with the gyro: the first one in mouse mode.
+fn gyro_stick(settings: &StickSettings) -> Option<Stick> {
+    if !get_config().gyro_enabled {
+        return None;
+    }
+    [Stick::Left, Stick::Right]
+        .into_iter()
+        .find(|stick| settings.stick_mode(*stick) == StickMode::Mouse)
+}
+
+/// Returns how the sticks in directional mode fire their directions, as configured for a controller.
+fn directional_settings(settings: &StickSettings) -> stick::DirectionalSettings {
+    stick::DirectionalSettings {
+        dead_zone: settings.right_stick_dead_zone,
+        trigger_zone: settings.right_stick_trigger_zone,
+        hysteresis: get_config().stick_hysteresis(settings),
+        directions: get_config().stick_directions,
+        diagonal_bias: get_config().right_stick_diagonal_bias,
+    }
+}
+
+/// Remembers the stick position farthest from the center since the last poll.
+fn track_stick_peak(pad: &Pad, stick: Stick) {
+    let coord = pad.stick_coord(stick);
+    let peak = pad.stick_peak(stick);
+
+    if coord.distance_to_origin() > peak.distance_to_origin() {
+        let (x, y) = coord.load_pair();
+        peak.store_pair(x, y);
+    }
+}
+
+/// Presses or releases the directional inputs of a stick, for one poll.
+///
+/// # Arguments
+///
+/// * `pad` - The controller of the stick.
+/// * `stick` - The stick firing directional inputs.
+/// * `settings` - The stick settings of the controller.
+/// * `pressed_input_name` - The direction held since the previous polls, if any.
+///
+/// # Returns
+///
+/// The direction held after this poll, if any.
+async fn trigger_stick_direction(
+    pad: &'static Pad,
+    stick: Stick,
+    settings: &StickSettings,
+    mut pressed_input_name: Option<&'static str>,
+) -> Option<&'static str> {
+    let (x, y) = pad.stick_coord(stick).load_pair();
+    let distance_to_origin = (x * x + y * y).sqrt();
+    let (peak_x, peak_y) = pad.stick_peak(stick).take_pair();
+    let peak_distance_to_origin = (peak_x * peak_x + peak_y * peak_y).sqrt();
+
+    let settings = directional_settings(settings);
+
+    match (pressed_input_name, stick::pushed_direction(x, y, pressed_input_name.is_some(), &settings)) {
+        (Some(input_name), None) => {
+            press_input(pad, input_name, false).await;
+            pressed_input_name = None;
+        }
+        (None, None) => {
+            if get_config().right_stick_flick_detection
+                && distance_to_origin <= settings.dead_zone
+                && peak_distance_to_origin >= settings.trigger_zone
+            {
+                // The stick went past the trigger zone and back between two polls
+                let input_name = stick::direction(peak_x, peak_y, &settings).input_name(stick);
+
+                *pad.last_direction_instant.lock().unwrap() = Some(Instant::now());
+                press_input(pad, input_name, true).await;
+                press_input(pad, input_name, false).await;
+            }
+        }
+        (Some(input_name), Some(direction)) => {
+            let pushed_input_name = direction.input_name(stick);
+
+            if get_config().socd_resolution != SocdResolution::None
+                && distance_to_origin >= settings.trigger_zone
+                && names::opposite_input_name(input_name) == Some(pushed_input_name)
+            {
+                // The stick went to the opposite direction between two polls
+                *pad.last_direction_instant.lock().unwrap() = Some(Instant::now());
+                press_input(pad, input_name, false).await;
+                press_input(pad, pushed_input_name, true).await;
+                pressed_input_name = Some(pushed_input_name);
+            }
+        }
+        (None, Some(direction)) => {
+            let input_name = direction.input_name(stick);
+
+            *pad.last_direction_instant.lock().unwrap() = Some(Instant::now());
+            press_input(pad, input_name, true).await;
+            pressed_input_name = Some(input_name);
+        }
+    }
+    pressed_input_name
+}
+
+/// Polls a stick of a controller forever, using it as configured by its `StickMode`.
+///
+/// The stick is only polled at its interval while it is out of its dead zone.
+/// Otherwise, the loop waits for the stick to move, so an idle controller costs
+/// no CPU time.
+async fn poll_stick(pad: &'static Pad, stick: Stick) {
+    let mut mouse_ticks = 0;
+    let mut pressed_input_name = None;
+    let mut absolute_position = None;
+    // The interval slept since the previous poll moving the mouse, unknown after waiting for the stick
+    let mut mouse_interval = None;
+    let mut last_mouse_position = (0., 0.);
+    let mut mouse_batch = MouseBatch::default();
+
+    loop {
+        let coord = pad.stick_coord(stick);
+        let settings = pad_stick_settings(pad);
+        let display_size = match settings.stick_mode(stick) {
+            StickMode::Absolute => main_display_size(),
+            _ => None,
+        };
+        let (interval, is_idle) = match (settings.stick_mode(stick), display_size) {
+            (StickMode::Mouse, _) | (StickMode::Absolute, None) => {
+                // The mode may have changed on a config reload or a reconnect
+                if let Some(input_name) = pressed_input_name.take() {
+                    press_input(pad, input_name, false).await;
+                }
+                let base_interval = stick_poll_interval(get_config().left_stick_poll_interval);
+                let elapsed = mouse_interval.unwrap_or(base_interval);
+                // Shorter polls move the mouse less, so its speed does not depend on the interval
+                let scale = elapsed.as_secs_f32() / base_interval.as_secs_f32();
+                let gyro = (gyro_stick(&settings) == Some(stick)).then_some(&pad.gyro_delta);
+                mouse_batch.elapsed += elapsed;
+                mouse_ticks = move_mouse_with_stick(pad, stick, &settings, gyro, scale, mouse_ticks, &mut mouse_batch);
+
+                let position = coord.load_pair();
+                let next_interval = adapt_poll_interval(
+                    elapsed,
+                    base_interval,
+                    get_config().left_stick_poll_interval_min,
+                    position != last_mouse_position,
+                );
+                last_mouse_position = position;
+                let is_idle = coord.distance_to_origin() <= settings.left_stick_dead_zone;
+                mouse_interval = (!is_idle).then_some(next_interval);
+                (next_interval, is_idle)
+            }
+            (StickMode::Absolute, Some(display_size)) => {
+                if let Some(input_name) = pressed_input_name.take() {
+                    press_input(pad, input_name, false).await;
+                }
+                let is_lagging;
+                (absolute_position, is_lagging) =
+                    move_mouse_to_stick(pad, coord, &settings, display_size, absolute_position);
+                // The position only changes with the stick, so there is nothing to poll unless the
+                // mouse must catch up with it once unfrozen
+                (stick_poll_interval(get_config().left_stick_poll_interval), !is_lagging)
+            }
+            (StickMode::Scroll, _) => {
+                if let Some(input_name) = pressed_input_name.take() {
+                    press_input(pad, input_name, false).await;
+                }
+                let is_scrolling = scroll_with_stick(coord, pad.stick_mouse_remainder(stick), &settings);
+                (stick_poll_interval(get_config().right_stick_poll_interval), !is_scrolling)
+            }
+            (StickMode::Directional, _) => {
+                pressed_input_name = trigger_stick_direction(pad, stick, &settings, pressed_input_name).await;
+                (
+                    stick_poll_interval(get_config().right_stick_poll_interval),
+                    pressed_input_name.is_none()
+                        && coord.distance_to_origin() <= settings.right_stick_dead_zone,
+                )
+            }
+        };
+
+        if is_idle {
+            pad.stick_notify(stick).notified().await;
+        } else {
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Clamps an axis value reported by the driver to `stick_value_limit`.
+///
+/// A warning is logged the first time a value is out of range, since it
+/// means the driver is misbehaving.
+fn clamp_axis_value(axis: StickAxis, value: f32) -> f32 {
+    let limit = get_config().stick_value_limit;
+    let clamped_value = stick::clamp_axis(value, limit);
+
+    if clamped_value != value && !HAS_LOGGED_CLAMPING.swap(true, Ordering::Relaxed) {
+        log::warn!("The driver reported {value} for {axis:?}, clamping stick values to ±{limit}");
+    }
+    clamped_value
+}
+
+/// Returns the value stored for a stick axis: clamped, recentered, then negated if the axis is inverted.
+///
+/// The loops use the stored value as is, so the correction applies to the mouse
+/// movement and the directions alike.
+fn stick_axis_value(axis: StickAxis, value: f32) -> f32 {
+    let value = clamp_axis_value(axis, value) - get_config().axis_center_offset(axis);
+
+    if get_config().is_axis_inverted(axis) {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Decides whether an analog input is pressed after moving to `value`.
+///
+/// # Arguments
+///
+/// * `was_pressed` - Whether the input was pressed before moving.
+/// * `value` - The new trigger pull or stick distance.
+/// * `threshold` - The value at which the input is pressed.
+/// * `hysteresis` - How far below the threshold a pressed input must fall to be released.
+fn is_past_threshold(was_pressed: bool, value: f32, threshold: f32, hysteresis: f32) -> bool {
+    if was_pressed {
+        value > threshold - hysteresis
+    } else {
+        value >= threshold
+    }
+}
+
+/// Checks whether the digital events of a button are replaced by its analog pull.
+fn is_analog_trigger(button: &str) -> bool {
+    get_config().trigger_activation_threshold.is_some()
+        && [Trigger::LeftTrigger, Trigger::RightTrigger]
+            .iter()
+            .any(|trigger| trigger.input_name() == button)
+}
+
+/// Checks whether the events of a button are left to the system instead of being remapped.
+fn is_left_to_system(button: &str) -> bool {
+    if button != "mode" {
+        return false;
+    }
+    // The system may also handle the guide button, so whether it is received is worth knowing
+    log::debug!("Received the mode button, intercepted: {}", get_config().intercept_mode_button);
+    !get_config().intercept_mode_button
+}
+
+/// Presses or releases an analog trigger when its pull crosses the threshold.
+async fn handle_trigger_changed(pad: &'static Pad, trigger: Trigger, value: f32) {
+    pad.trigger_pull(trigger).store(value);
+
+    if let Some(threshold) = get_config().trigger_activation_threshold {
+        let input_name = trigger.input_name();
+        let was_pressed = pad.held_inputs.lock().unwrap().contains(input_name);
+        let is_pressed = is_past_threshold(was_pressed, value, threshold, get_config().trigger_hysteresis());
+
+        if is_pressed != was_pressed {
+            press_input(pad, input_name, is_pressed).await;
+        }
+    }
+}
+
+/// Handles an event of a controller, whichever source it comes from.
+///
+/// The first event of a controller starts the loops polling its sticks.
+///
+/// # Arguments
+///
+/// * `pad_id` - The controller the event comes from.
+/// * `event` - The event, as reported by the gilrs loop or read from a replay file.
+async fn handle_event(pad_id: PadId, event: InputEvent) {
+    let pad = get_pad(pad_id);
+
+    if !pad.is_polled.swap(true, Ordering::Relaxed) {
+        tokio::spawn(poll_stick(pad, Stick::Left));
+        tokio::spawn(poll_stick(pad, Stick::Right));
+    }
+
+    match event {
+        InputEvent::Connected { name } => handle_connected(pad, name).await,
+        InputEvent::Disconnected => handle_disconnected(pad, get_config().disconnect_debounce).await,
+        InputEvent::ButtonPressed { button } | InputEvent::ButtonReleased { button }
+            if is_analog_trigger(&button) || is_left_to_system(&button) => {}
+        InputEvent::ButtonPressed { button } => press_input(pad, &button, true).await,
+        InputEvent::ButtonReleased { button } => press_input(pad, &button, false).await,
+        InputEvent::TriggerChanged { trigger, value } => handle_trigger_changed(pad, trigger, value).await,
+        InputEvent::GyroMoved { x, y } => {
+            if let Some(stick) = gyro_stick(&pad_stick_settings(pad)) {
+                pad.gyro_delta.x.add(x);
+                pad.gyro_delta.y.add(y);
+                pad.stick_notify(stick).notify_one();
+            }
+        }
+        InputEvent::AxisChanged { axis, value } => match (axis, stick_axis_value(axis, value)) {
+            (StickAxis::LeftStickX, value) => {
+                pad.left_stick_coord.store_x(value);
+                track_stick_peak(pad, Stick::Left);
+                pad.left_stick_notify.notify_one();
+            }
+            (StickAxis::LeftStickY, value) => {
+                pad.left_stick_coord.store_y(value);
+                track_stick_peak(pad, Stick::Left);
+                pad.left_stick_notify.notify_one();
+            }
+            (StickAxis::RightStickX, value) => {
+                pad.right_stick_coord.store_x(value);
+                track_stick_peak(pad, Stick::Right);
+                pad.right_stick_notify.notify_one();
+            }
+            (StickAxis::RightStickY, value) => {
+                pad.right_stick_coord.store_y(value);
+                track_stick_peak(pad, Stick::Right);
+                pad.right_stick_notify.notify_one();
+            }
+        },
+    }
+}
+
+/// Sets up logging to the standard error.
+///
+/// As a service, the timestamps are left to the service manager and every
+/// line is prefixed with its syslog priority, which systemd's journal parses.
+fn init_logger(is_service: bool) {
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+
+    if is_service {
+        builder.format(|buf, record| {
+            let priority = match record.level() {
+                log::Level::Error => 3,
+                log::Level::Warn => 4,
+                log::Level::Info => 6,
+                log::Level::Debug | log::Level::Trace => 7,
+            };
+            writeln!(buf, "<{priority}>{}", record.args())
+        });
+    }
+    builder.init();
+}
+
+/// Waits until the application is asked to stop.
+#[cfg(unix)]
+async fn shutdown_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        _ = terminate.recv() => (),
+        _ = interrupt.recv() => (),
+    }
+    Ok(())
+}
+
+/// Waits until the application is asked to stop.
+#[cfg(windows)]
+async fn shutdown_signal() -> std::io::Result<()> {
+    use tokio::signal::windows::{ctrl_break, ctrl_c, ctrl_close, ctrl_shutdown};
+
+    let mut ctrl_c = ctrl_c()?;
+    let mut ctrl_break = ctrl_break()?;
+    let mut ctrl_close = ctrl_close()?;
+    let mut ctrl_shutdown = ctrl_shutdown()?;
+    tokio::select! {
+        _ = ctrl_c.recv() => (),
+        _ = ctrl_break.recv() => (),
+        _ = ctrl_close.recv() => (),
+        _ = ctrl_shutdown.recv() => (),
+    }
+    Ok(())
+}
+
+/// Releases everything once a stop is requested, then exits.
+///
+/// The event loop blocks while waiting for controller events, so the process
+/// is exited from here rather than by returning from `main`.
+async fn shutdown_on_signal() {
+    if let Err(err) = shutdown_signal().await {
+        log::error!("Cannot listen to shutdown signals: {err}");
+        return;
+    }
+    log::info!("Stopping, releasing held inputs");
+
+    for pad in get_pads() {
+        let held_directions = pad
+            .held_inputs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|input_name| !names::is_button_input_name(input_name))
+            .cloned()
+            .collect::<Vec<_>>();
+        for input_name in held_directions {
+            press_input(pad, &input_name, false).await;
+        }
+    }
+    reset_all_controller_states().await;
+    // Exiting drops the inputs the worker has not injected yet
+    get_input_sender().flush();
+    std::process::exit(0);
+}
+
+/// Largest number of runtime worker threads used when `worker_threads` is not configured.
+///
+/// The tasks are short and mostly wait on timers, so more threads would only
+/// cost power.
+const MAX_DEFAULT_WORKER_THREADS: usize = 3;
+
+/// Returns the number of runtime worker threads, as configured or detected from the CPU count.
+fn worker_threads() -> usize {
+    get_config().worker_threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map_or(1, |threads| threads.get())
+            .min(MAX_DEFAULT_WORKER_THREADS)
+    })
+}
+
+/// Runs the action given on the command line, remapping the controllers by default.
+pub fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli::Args { action, is_dry_run } = cli::parse_args(std::env::args().skip(1))?;
+    init_logger(action == Action::Service);
+    IS_DRY_RUN.store(is_dry_run, Ordering::Relaxed);
+
+    match action {
+        Action::ListInputs => {
+            names::input_names().iter().for_each(|name| println!("{name}"));
+            Ok(())
+        }
+        Action::ListKeys => {
+            names::key_names().iter().for_each(|name| println!("{}", names::key_usage(name)));
+            Ok(())
+        }
+        Action::Calibrate => {
+            print!("{}", calibration::calibrate(calibration::CALIBRATION_DURATION)?);
+            Ok(())
+        }
+        Action::GenerateConfig { is_forced } => {
+            let path = config_path();
+            generate_config(&path, is_forced)?;
+            println!("Wrote {}", path.display());
+            Ok(())
+        }
+        Action::Run | Action::Replay(_) | Action::Service => tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(worker_threads())
+            .enable_all()
+            .build()?
+            .block_on(run(action)),
+    }
+}
+
+/// Remaps the events of the controllers, or of a replay, until the source is exhausted.
+async fn run(action: Action) -> Result<(), Box<dyn std::error::Error>> {
+    let instance = SingleInstance::new(
+        &std::env::current_exe()
+            .unwrap()
+            .file_name()
+            .unwrap()
+            .to_string_lossy(),
+    )?;
+    if !instance.is_single() {
+        log::info!("Already running, exiting");
+        return Ok(());
+    }
+
+    // Ignore terminal process state
+    #[cfg(target_os = "linux")]
+    unsafe {
+        use nix::sys::signal::*;
+        sigaction(
+            Signal::SIGCHLD,
+            &SigAction::new(
+                SigHandler::SigDfl,
+                SaFlags::SA_NOCLDWAIT,
+                SigSet::empty(),
+            ),
+        )?;
+    }
+
+    #[cfg(target_os = "linux")]
+    if !IS_DRY_RUN.load(Ordering::Relaxed) {
+        let session_type = std::env::var("XDG_SESSION_TYPE").ok();
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP").ok();
+        if let Some(warning) = backend::session_warning(session_type.as_deref(), desktop.as_deref()) {
+            log::warn!("{warning}, see the Linux Sessions section of the README");
+        }
+    }
+
+    tokio::spawn(shutdown_on_signal());
+    tokio::spawn(watch_config());
+    tokio::spawn(watch_power());
+    tokio::spawn(watch_focus());
+    tokio::spawn(watch_display());
+
+    let mut source: Box<dyn EventSource> = match action {
+        Action::Replay(path) => Box::new(event::ReplaySource::open(&path)?),
+        _ => Box::new(event::GilrsSource::new()?),
+    };
+    while let Some((pad_id, event)) = source.next_event() {
+        handle_event(pad_id, event).await;
+    }
+
+    // Leave nothing pressed once a replay is over
+    reset_all_controller_states().await;
+    get_input_sender().flush();
+    Ok(())
+}
+
+/// Entry points for the benchmarks, which drive the remapping without a controller or a desktop.
+pub mod bench {
+    use super::*;
+
+    /// Replaces the current config, and makes the remaps log their input instead of injecting it.
+    ///
+    /// # Returns
+    ///
+    /// Whether the config was replaced.
+    pub async fn load_config(config_str: &str) -> bool {
+        IS_DRY_RUN.store(true, Ordering::Relaxed);
+        reload_config(config_str).await
+    }
+
+    /// Presses or releases an input of a controller, as its events do.
+    pub async fn press_input(pad_id: PadId, input_name: &str, is_press_down: bool) {
+        super::press_input(get_pad(pad_id), input_name, is_press_down).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::Output;
+
+    /// Returns the inputs injected by the mock backend, once the ones sent so far reach it.
+    fn take_outputs() -> Vec<Output> {
+        get_input_sender().flush();
+        backend::mock::take_outputs()
+    }
+
+    /// Has the worker measure the main display of the mock backend again, and waits for it.
+    fn refresh_main_display() {
+        get_input_sender().refresh_main_display();
+        get_input_sender().flush();
+    }
+
+    /// Serializes the tests touching the global input state.
+    static STATE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Holds the global input state for a test, with its own config, until dropped.
+    ///
+    /// The default config is put back on drop, even when the test panics, so that
+    /// a failing test cannot leak its config into the tests run after it.
+    struct ConfigGuard {
+        _lock: tokio::sync::MutexGuard<'static, ()>,
+    }
+
+    impl ConfigGuard {
+        /// Locks the global input state, loads a config and discards the outputs of the previous tests.
+        ///
+        /// Loading the config also resets the controllers, left as they were by the previous test.
+        async fn new(config: &str) -> Self {
+            let lock = STATE_LOCK.lock().await;
+            assert!(reload_config(config).await);
+            take_outputs();
+            Self { _lock: lock }
+        }
+    }
+
+    impl Drop for ConfigGuard {
+        fn drop(&mut self) {
+            // Dropping cannot wait for the controllers to be reset, which the next guard does
+            if let Ok(mut config) = CONFIG.get().unwrap().write() {
+                *config = Box::leak(Box::new(parse_config("").unwrap()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_baseline() {
+        let _lock = STATE_LOCK.lock().await;
+        press_input(get_pad(0), "", true).await;
+        // Note: the poll_stick loops are infinity;
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_flap_keeps_state() {
+        let _config = ConfigGuard::new("").await;
+        let debounce = Duration::from_millis(50);
+        let pad = get_pad(0);
+
+        pad.is_alternative_active.store(true, Ordering::Relaxed);
+        pad.left_stick_coord.store_x(0.5);
+
+        // A brief disconnect followed by a reconnect keeps the state
+        handle_disconnected(pad, debounce).await;
+        handle_connected(pad, None).await;
+        tokio::time::sleep(2 * debounce).await;
+        assert!(pad.is_alternative_active.load(Ordering::Relaxed));
+        assert_eq!(pad.left_stick_coord.load_pair().0, 0.5);
+
+        // Repeated flaps restart the window instead of piling up resets
+        handle_disconnected(pad, debounce).await;
+        handle_disconnected(pad, debounce).await;
+        handle_connected(pad, None).await;
+        tokio::time::sleep(2 * debounce).await;
+        assert!(pad.is_alternative_active.load(Ordering::Relaxed));
+
+        // A sustained disconnect resets it
+        handle_disconnected(pad, debounce).await;
+        tokio::time::sleep(2 * debounce).await;
+        assert!(!pad.is_alternative_active.load(Ordering::Relaxed));
+        assert_eq!(pad.left_stick_coord.load_pair().0, 0.);
+    }
+
+    #[test]
+    fn test_coordinate() {
+        let coord = Coordinate::new();
+        coord.store_x(0.5);
+        coord.store_y(-0.25);
+        assert_eq!(coord.load_pair(), (0.5, -0.25));
+        coord.store_pair(-1., f32::NAN);
+        assert_eq!(coord.take_pair(), (-1., 0.));
+        assert_eq!(coord.load_pair(), (0., 0.));
+
+        // Pairs stored together are always read together
+        let is_done = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..100_000 {
+                    coord.store_pair(i as f32, -(i as f32));
+                }
+                is_done.store(true, Ordering::Relaxed);
+            });
+            scope.spawn(|| {
+                while !is_done.load(Ordering::Relaxed) {
+                    let (x, y) = coord.load_pair();
+                    assert_eq!(x, -y);
+                }
+            });
+        });
+
+        // Axes stored at the same time keep each other's value
+        std::thread::scope(|scope| {
+            scope.spawn(|| (0..=10_000).for_each(|i| coord.store_x(i as f32)));
+            scope.spawn(|| (0..=10_000).for_each(|i| coord.store_y(-i as f32)));
+        });
+        assert_eq!(coord.load_pair(), (10_000., -10_000.));
+    }
+
+    #[test]
+    fn test_adapt_poll_interval() {
+        let base = Duration::from_millis(10);
+        let min = Some(Duration::from_millis(2));
+
+        assert_eq!(adapt_poll_interval(base, base, None, true), base);
+        assert_eq!(adapt_poll_interval(base, base, min, true), Duration::from_millis(5));
+        assert_eq!(adapt_poll_interval(Duration::from_millis(3), base, min, true), Duration::from_millis(2));
+        assert_eq!(adapt_poll_interval(Duration::from_millis(2), base, min, false), Duration::from_millis(4));
+        assert_eq!(adapt_poll_interval(Duration::from_millis(8), base, min, false), base);
+
+        // A minimum above the base interval, e.g. on battery, is ignored
+        assert_eq!(adapt_poll_interval(base, Duration::from_millis(1), min, true), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_trigger_hysteresis() {
+        assert!(!is_past_threshold(false, 0.4, 0.5, 0.05));
+        assert!(is_past_threshold(false, 0.5, 0.5, 0.05));
+
+        // Small wobbles around the threshold keep the trigger pressed
+        assert!(is_past_threshold(true, 0.48, 0.5, 0.05));
+        assert!(!is_past_threshold(true, 0.45, 0.5, 0.05));
+        assert!(!is_past_threshold(false, 0.48, 0.5, 0.05));
+
+        // Without hysteresis, the trigger is released right below the threshold
+        assert!(!is_past_threshold(true, 0.48, 0.5, 0.));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stick_hysteresis() {
+        let _config = ConfigGuard::new("hysteresis = 0.125\n[main]\nright_stick_right = { sync = ['Tab'] }").await;
+        let axis_changed = |value| InputEvent::AxisChanged { axis: StickAxis::RightStickX, value };
+        let tab = |direction| Output::Key(enigo::Key::Tab, direction);
+
+        // Wobbling within the margin below the trigger zone keeps the direction pressed
+        for value in [0.5, 0.25, 0.5] {
+            handle_event(68, axis_changed(value)).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(take_outputs(), [tab(Direction::Press)]);
+
+        // Past the margin, it is released before reaching the dead zone
+        for value in [0.125, 0.5] {
+            handle_event(68, axis_changed(value)).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(take_outputs(), [tab(Direction::Release), tab(Direction::Press)]);
+
+        handle_event(68, axis_changed(0.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(take_outputs(), [tab(Direction::Release)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_stick_waits_for_events() {
+        let _config = ConfigGuard::new("").await;
+        let axis_changed = |value| InputEvent::AxisChanged { axis: StickAxis::LeftStickX, value };
+
+        // The loops start waiting as the stick is centered
+        handle_event(60, axis_changed(0.)).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(take_outputs().is_empty());
+
+        // Moving the stick wakes the mouse loop right away, without time passing
+        handle_event(60, axis_changed(1.)).await;
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(9, 0, enigo::Coordinate::Rel)]
+        );
+
+        handle_event(60, axis_changed(0.)).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        take_outputs();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mouse_move_flush_interval() {
+        let config = "mouse_move_flush_interval = '30ms'\nmouse_initial_speed = 4\nmouse_max_speed = 4";
+        let _config = ConfigGuard::new(config).await;
+        let axis_changed = |value| InputEvent::AxisChanged { axis: StickAxis::LeftStickX, value };
+
+        // The polls of every 10ms, of 3 then 4 pixels, are moved at once after 30ms of polling
+        handle_event(74, axis_changed(1.)).await;
+        tokio::time::sleep(Duration::from_millis(35)).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(11, 0, enigo::Coordinate::Rel)]
+        );
+
+        // Stopping the stick moves the rest right away
+        handle_event(74, axis_changed(0.)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(4, 0, enigo::Coordinate::Rel)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mouse_freeze_after_direction() {
+        let config = "mouse_freeze_after_direction = '200ms'\n[main]\nright_stick_right = { seq = ['Tab'] }";
+        let _config = ConfigGuard::new(config).await;
+        let axis_changed = |axis, value| InputEvent::AxisChanged { axis, value };
+        let is_mouse_moved =
+            |outputs: &[Output]| outputs.iter().any(|output| matches!(output, Output::MoveMouse(..)));
+
+        handle_event(79, axis_changed(StickAxis::RightStickX, 1.)).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        handle_event(79, axis_changed(StickAxis::RightStickX, 0.)).await;
+        assert!(take_outputs().contains(&Output::Key(enigo::Key::Tab, Direction::Press)));
+
+        // The left stick moves nothing until the window after the direction is over
+        handle_event(79, axis_changed(StickAxis::LeftStickX, 1.)).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let outputs = take_outputs();
+        assert!(!is_mouse_moved(&outputs), "{outputs:?}");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(is_mouse_moved(&take_outputs()));
+
+        // A direction of another controller leaves the mouse of this one moving
+        handle_event(80, axis_changed(StickAxis::RightStickX, 1.)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let outputs = take_outputs();
+        assert!(outputs.contains(&Output::Key(enigo::Key::Tab, Direction::Press)));
+        assert!(is_mouse_moved(&outputs));
+        handle_event(80, axis_changed(StickAxis::RightStickX, 0.)).await;
+        handle_event(79, axis_changed(StickAxis::LeftStickX, 0.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Without a window, the mouse moves right after the direction
+        assert!(reload_config("[main]\nright_stick_right = { seq = ['Tab'] }").await);
+        take_outputs();
+        handle_event(79, axis_changed(StickAxis::RightStickX, 1.)).await;
+        handle_event(79, axis_changed(StickAxis::LeftStickX, 1.)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let outputs = take_outputs();
+        assert!(outputs.contains(&Output::Key(enigo::Key::Tab, Direction::Press)));
+        assert!(is_mouse_moved(&outputs));
+        handle_event(79, axis_changed(StickAxis::RightStickX, 0.)).await;
+        handle_event(79, axis_changed(StickAxis::LeftStickX, 0.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        take_outputs();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_left_stick_directional() {
+        let config = "left_stick_mode = 'directional'\n[main]\nleft_stick_right = { seq = ['Tab'] }";
+        let _config = ConfigGuard::new(config).await;
+        let axis_changed = |value| InputEvent::AxisChanged { axis: StickAxis::LeftStickX, value };
+
+        // The left stick fires its directions like the right one instead of moving the mouse
+        handle_event(67, axis_changed(1.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle_event(67, axis_changed(0.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Key(enigo::Key::Tab, Direction::Press),
+                Output::Key(enigo::Key::Tab, Direction::Release)
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gamepad_stick_mode() {
+        let config = r#"
+            [main]
+            left_stick_right = { seq = ['Tab'] }
+            [gamepads.'Game Pad']
+            left_stick_mode = 'directional'
+        "#;
+        let _config = ConfigGuard::new(config).await;
+        let axis_changed = |value| InputEvent::AxisChanged { axis: StickAxis::LeftStickX, value };
+        handle_event(77, InputEvent::Connected { name: Some("Game Pad".to_string()) }).await;
+        take_outputs();
+
+        // Only the controller with its own stick mode fires directions, the other one moves the mouse
+        handle_event(77, axis_changed(1.)).await;
+        handle_event(78, axis_changed(1.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle_event(77, axis_changed(0.)).await;
+        handle_event(78, axis_changed(0.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let outputs = take_outputs();
+        assert!(outputs.contains(&Output::Key(enigo::Key::Tab, Direction::Press)));
+        assert!(outputs.contains(&Output::Key(enigo::Key::Tab, Direction::Release)));
+        assert!(outputs.iter().any(|output| matches!(output, Output::MoveMouse(_, 0, enigo::Coordinate::Rel))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_absolute_stick_after_freeze() {
+        let config = r#"
+            left_stick_mode = 'absolute'
+            mouse_freeze_after_direction = '200ms'
+            [main]
+            right_stick_right = { seq = ['Tab'] }
+        "#;
+        let _config = ConfigGuard::new(config).await;
+        let axis_changed = |axis, value| InputEvent::AxisChanged { axis, value };
+        handle_event(84, InputEvent::Connected { name: None }).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        handle_event(84, axis_changed(StickAxis::RightStickX, 1.)).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        handle_event(84, axis_changed(StickAxis::RightStickX, 0.)).await;
+        take_outputs();
+        handle_event(84, axis_changed(StickAxis::LeftStickX, 1.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let outputs = take_outputs();
+        assert!(!outputs.iter().any(|output| matches!(output, Output::MoveMouse(..))), "{outputs:?}");
+
+        // The mouse catches up with the stick once unfrozen, without the stick moving again
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let outputs = take_outputs();
+        assert!(matches!(outputs[..], [Output::MoveMouse(_, 540, enigo::Coordinate::Abs)]), "{outputs:?}");
+        handle_event(84, axis_changed(StickAxis::LeftStickX, 0.)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_absolute_stick() {
+        let _config = ConfigGuard::new("left_stick_mode = 'absolute'").await;
+        let axis_changed = |value| InputEvent::AxisChanged { axis: StickAxis::LeftStickX, value };
+
+        handle_event(81, axis_changed(1.)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let outputs = take_outputs();
+        assert!(matches!(outputs[..], [Output::MoveMouse(_, 540, enigo::Coordinate::Abs)]), "{outputs:?}");
+        handle_event(81, axis_changed(0.)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        take_outputs();
+
+        // Without the display size, as on Wayland, the stick moves the mouse relatively
+        backend::mock::set_main_display(None);
+        refresh_main_display();
+        handle_event(81, axis_changed(1.)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        handle_event(81, axis_changed(0.)).await;
+        let outputs = take_outputs();
+        backend::mock::set_main_display(Some((1920, 1080)));
+        refresh_main_display();
+        assert!(!outputs.is_empty());
+        assert!(outputs.iter().all(|output| matches!(output, Output::MoveMouse(_, 0, enigo::Coordinate::Rel))));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stick_scroll() {
+        let config = r#"
+            right_stick_mode = 'scroll'
+            right_stick_dead_zone = 0.5
+            right_stick_trigger_zone = 0.5
+            stick_scroll_speed = 4
+        "#;
+        let _config = ConfigGuard::new(config).await;
+        let axis_changed = |axis, value| InputEvent::AxisChanged { axis, value };
+
+        // Half of the push is past the dead zone, scrolling half the speed on every poll
+        handle_event(70, axis_changed(StickAxis::RightStickY, 1.)).await;
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        handle_event(70, axis_changed(StickAxis::RightStickY, 0.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(take_outputs(), vec![Output::Scroll(-2, enigo::Axis::Vertical); 3]);
+
+        handle_event(70, axis_changed(StickAxis::RightStickX, -1.)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        handle_event(70, axis_changed(StickAxis::RightStickX, 0.)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(take_outputs(), [Output::Scroll(-2, enigo::Axis::Horizontal)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gyro_moves_mouse() {
+        let _config = ConfigGuard::new("gyro_enabled = true").await;
+
+        handle_event(62, InputEvent::GyroMoved { x: 1., y: 0.55 }).await;
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(10, -5, enigo::Coordinate::Rel)]
+        );
+
+        // The fraction of a pixel left is carried to the next movement
+        handle_event(62, InputEvent::GyroMoved { x: 0., y: 0.05 }).await;
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(0, -1, enigo::Coordinate::Rel)]
+        );
+
+        // Without the inversion, turning up moves the mouse down the screen
+        assert!(reload_config("gyro_enabled = true\nmouse_invert_y = false").await);
+        handle_event(62, InputEvent::GyroMoved { x: 0., y: 0.5 }).await;
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(0, 5, enigo::Coordinate::Rel)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mode_button() {
+        let _config = ConfigGuard::new("[main]\nmode = { sync = ['Tab'] }").await;
+        let pad = get_pad(69);
+        let mode = |is_pressed| {
+            let button = "mode".to_string();
+            if is_pressed { InputEvent::ButtonPressed { button } } else { InputEvent::ButtonReleased { button } }
+        };
+
+        handle_event(69, mode(true)).await;
+        handle_event(69, mode(false)).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Key(enigo::Key::Tab, Direction::Press),
+                Output::Key(enigo::Key::Tab, Direction::Release)
+            ]
+        );
+
+        // Left to the system, the button is not even tracked as held
+        assert!(reload_config("intercept_mode_button = false").await);
+        handle_event(69, mode(true)).await;
+        assert!(!pad.held_inputs.lock().unwrap().contains("mode"));
+        handle_event(69, mode(false)).await;
+    }
+
+    #[tokio::test]
+    async fn test_non_finite_axis_values() {
+        let _config = ConfigGuard::new("").await;
+        let pad = get_pad(71);
+
+        // Some drivers report NaN on disconnect, which would otherwise reach the mouse movement
+        for value in [f32::NAN, f32::INFINITY] {
+            handle_event(71, InputEvent::AxisChanged { axis: StickAxis::RightStickY, value: 0.5 }).await;
+            handle_event(71, InputEvent::AxisChanged { axis: StickAxis::RightStickY, value }).await;
+            assert_eq!(pad.right_stick_coord.load_pair().1, 0.);
+            handle_event(71, InputEvent::TriggerChanged { trigger: Trigger::LeftTrigger, value }).await;
+            assert_eq!(pad.left_trigger_pull.load(), 0.);
+        }
+        reset_controller_state(pad).await;
+    }
+
+    #[tokio::test]
+    async fn test_pads_are_independent() {
+        let _config = ConfigGuard::new("").await;
+        let first = get_pad(10);
+        let second = get_pad(11);
+
+        handle_event(10, InputEvent::AxisChanged { axis: StickAxis::LeftStickX, value: 0.5 }).await;
+        first.is_alternative_active.store(true, Ordering::Relaxed);
+        assert_eq!(second.left_stick_coord.load_pair().0, 0.);
+        assert!(!second.is_alternative_active());
+
+        // Disconnecting a controller leaves the other untouched
+        handle_event(11, InputEvent::AxisChanged { axis: StickAxis::LeftStickX, value: -0.5 }).await;
+        handle_disconnected(first, Duration::ZERO).await;
+        assert_eq!(first.left_stick_coord.load_pair().0, 0.);
+        assert_eq!(second.left_stick_coord.load_pair().0, -0.5);
+        reset_controller_state(second).await;
+    }
+
+    /// Parses a remap that lives as long as the global config does.
+    fn parse_remap(remap: &str) -> &'static Remap {
+        &Box::leak(Box::new(toml::from_str::<Binding>(remap).unwrap())).remap
+    }
+
+    /// Fires the events of a replay script through `handle_event` at their times, on the paused clock.
+    ///
+    /// # Returns
+    ///
+    /// The outputs of the backend, once the last event was handled.
+    async fn play_script(script: &str) -> Vec<Output> {
+        let start = tokio::time::Instant::now();
+        let events = event::ReplaySource::from_reader(script.as_bytes()).unwrap().into_timed_events();
+
+        for (at, pad_id, event) in events {
+            tokio::time::sleep_until(start + at).await;
+            handle_event(pad_id, event).await;
+        }
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        take_outputs()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_play_script() {
+        let config = r#"
+            [main]
+            south = { sync = ['Space'] }
+            east = { sync = ['Return'] }
+            right_stick_right = { sync = ['Tab'] }
+        "#;
+        let _config = ConfigGuard::new(config).await;
+        let key = |key, direction| Output::Key(key, direction);
+
+        // A session from connection to disconnection, through the same dispatch as the controllers
+        let script = r#"
+            {"at_ms": 0, "pad": 75, "type": "connected", "name": "Test Pad"}
+            {"at_ms": 0, "pad": 75, "type": "button_pressed", "button": "south"}
+            {"at_ms": 50, "pad": 75, "type": "button_released", "button": "south"}
+            {"at_ms": 100, "pad": 75, "type": "axis_changed", "axis": "right_stick_x", "value": 1.0}
+            {"at_ms": 200, "pad": 75, "type": "axis_changed", "axis": "right_stick_x", "value": 0.0}
+            {"at_ms": 300, "pad": 75, "type": "button_pressed", "button": "east"}
+            {"at_ms": 350, "pad": 75, "type": "disconnected"}
+        "#;
+        assert_eq!(
+            play_script(script).await,
+            [
+                key(enigo::Key::Space, Direction::Press),
+                key(enigo::Key::Space, Direction::Release),
+                key(enigo::Key::Tab, Direction::Press),
+                key(enigo::Key::Tab, Direction::Release),
+                key(enigo::Key::Return, Direction::Press),
+                key(enigo::Key::Return, Direction::Release)
+            ]
+        );
+        assert_eq!(get_pad(75).name.lock().unwrap().as_deref(), Some("Test Pad"));
+    }
+
+    #[test]
+    fn test_parse_config_error() {
+        let err = parse_config("key_repeat_sub_delay = '40ms'\n[main]\nsouth = { seq = 'Space' }").unwrap_err();
+        assert!(err.contains("line 3"), "{err}");
+
+        let err = parse_config("left_stick_dead_zone = 0").unwrap_err();
+        assert_eq!(err, "Invalid config file: left_stick_dead_zone (0) must be positive");
+
+        let err = parse_config("[main]\ndpadup = { seq = ['Space'] }\nsouth = { sync = [] }").unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid config file: Unknown input in main: dpadup; Empty key list in main.south"
+        );
+
+        let err = parse_config("intercept_mode_button = false\n[alt]\nmode = { seq = ['Space'] }").unwrap_err();
+        assert_eq!(err, "Invalid config file: Mode button remapped without intercept_mode_button in alt");
+    }
+
+    #[tokio::test]
+    async fn test_reload_config() {
+        let _config = ConfigGuard::new("").await;
+        let default_sub_delay = get_config().key_repeat_sub_delay;
+
+        assert!(!reload_config("key_repeat_sub_delay = 'soon'").await);
+        assert!(!reload_config("right_stick_dead_zone = 0.5\nright_stick_trigger_zone = 0.2").await);
+        assert_eq!(get_config().key_repeat_sub_delay, default_sub_delay);
+
+        assert!(reload_config("key_repeat_sub_delay = '10ms'").await);
+        assert_eq!(get_config().key_repeat_sub_delay, Duration::from_millis(10));
+
+        assert!(reload_config("").await);
+        assert_eq!(get_config().key_repeat_sub_delay, default_sub_delay);
+    }
+
+    #[tokio::test]
+    async fn test_switch_profile() {
+        let _config = ConfigGuard::new("").await;
+        let pad = get_pad(63);
+        let profile = std::env::temp_dir().join("dogmu_test_profile.toml");
+        std::fs::write(&profile, "key_repeat_sub_delay = '10ms'").unwrap();
+        assert!(reload_config(&format!("[main]\nsouth = {{ switch_profile = '{}' }}", profile.display())).await);
+
+        press_input(pad, "south", true).await;
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(get_config().key_repeat_sub_delay, Duration::from_millis(10));
+        assert_eq!(active_config_path(), profile);
+        // The switch released the button as the previous config mapped it
+        assert!(pad.held_inputs.lock().unwrap().is_empty());
+
+        *ACTIVE_CONFIG_PATH.get().unwrap().lock().unwrap() = config_path();
+        std::fs::remove_file(profile).unwrap();
+    }
+
+    #[test]
+    fn test_generate_config() {
+        let path = std::env::temp_dir().join("dogmu_test_generated.toml");
+        let _ = std::fs::remove_file(&path);
+
+        generate_config(&path, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), config::CONFIG_TEMPLATE);
+        assert!(parse_config(config::CONFIG_TEMPLATE).is_ok());
+
+        // An existing file is only overwritten when forced
+        std::fs::write(&path, "mouse_max_speed = 30.0").unwrap();
+        let err = generate_config(&path, false).unwrap_err();
+        assert!(err.ends_with("already exists, pass --force to overwrite it"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "mouse_max_speed = 30.0");
+        generate_config(&path, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), config::CONFIG_TEMPLATE);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_key_down_and_up() {
+        let _config = ConfigGuard::new("").await;
+        let key_down = parse_remap("key_down = 'Shift'");
+        let key_up = parse_remap("key_up = 'Shift'");
+        let shift = |direction| Output::Key(enigo::Key::Shift, direction);
+
+        // The key stays down after the release of the input, until another remap releases it
+        fire_remap(get_pad(0), "south", key_down, true).await;
+        fire_remap(get_pad(0), "south", key_down, false).await;
+        assert_eq!(take_outputs(), [shift(Direction::Press)]);
+        fire_remap(get_pad(0), "east", key_up, true).await;
+        fire_remap(get_pad(0), "east", key_up, false).await;
+        assert_eq!(take_outputs(), [shift(Direction::Release)]);
+
+        // A reset releases the keys left down
+        fire_remap(get_pad(0), "south", key_down, true).await;
+        take_outputs();
+        reset_all_controller_states().await;
+        assert_eq!(take_outputs(), [shift(Direction::Release)]);
+        assert!(get_key_downs().lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_nudge() {
+        let _config = ConfigGuard::new("").await;
+        let nudge = parse_remap("nudge = { dx = 5, dy = -3 }");
+
+        fire_remap(get_pad(0), "test", nudge, true).await;
+        fire_remap(get_pad(0), "test", nudge, false).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(5, -3, enigo::Coordinate::Rel)]
+        );
+
+        // Holding the button does not repeat the nudge
+        fire_remap(get_pad(0), "test", nudge, true).await;
+        tokio::time::sleep(get_config().key_repeat_initial_delay * 2).await;
+        fire_remap(get_pad(0), "test", nudge, false).await;
+        assert_eq!(take_outputs().len(), 1);
+
+        let nudge = parse_remap("mouse_move = { dx = 1, dy = 0 }");
+        fire_remap(get_pad(0), "test", nudge, true).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(1, 0, enigo::Coordinate::Rel)]
+        );
+
+        let move_to = parse_remap("move_to = { x = 960, y = 540 }");
+        fire_remap(get_pad(0), "test", move_to, true).await;
+        fire_remap(get_pad(0), "test", move_to, false).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::MoveMouse(960, 540, enigo::Coordinate::Abs)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mouse_buttons() {
+        let _config = ConfigGuard::new("").await;
+
+        let mut names = vec!["Left", "Middle", "Right", "ScrollUp", "ScrollDown", "ScrollLeft", "ScrollRight"];
+        if cfg!(not(target_os = "macos")) {
+            names.extend(["Back", "Forward"]);
+        }
+        for name in names {
+            let mouse = parse_remap(&format!("mouse = '{name}'"));
+            let Remap::Mouse(button) = *mouse else {
+                panic!("{name} is not parsed as a mouse button");
+            };
+            assert_eq!(format!("{button:?}"), name);
+
+            fire_remap(get_pad(0), "test", mouse, true).await;
+            fire_remap(get_pad(0), "test", mouse, false).await;
+            assert_eq!(
+                take_outputs(),
+                [
+                    Output::Button(button, enigo::Direction::Press),
+                    Output::Button(button, enigo::Direction::Release)
+                ]
+            );
+        }
+
+        let click = parse_remap("mouse_click = 'Middle'");
+        fire_remap(get_pad(0), "test", click, true).await;
+        fire_remap(get_pad(0), "test", click, false).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::Button(enigo::Button::Middle, enigo::Direction::Click)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scroll() {
+        let _config = ConfigGuard::new("").await;
+
+        let scroll = parse_remap("scroll = { y = -3 }");
+        fire_remap(get_pad(0), "test", scroll, true).await;
+        fire_remap(get_pad(0), "test", scroll, false).await;
+        assert_eq!(take_outputs(), [Output::Scroll(-3, enigo::Axis::Vertical)]);
+
+        let scroll = parse_remap("scroll = { x = 2, y = 1 }");
+        fire_remap(get_pad(0), "test", scroll, true).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Scroll(2, enigo::Axis::Horizontal),
+                Output::Scroll(1, enigo::Axis::Vertical)
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scroll_repeat() {
+        let _config = ConfigGuard::new("").await;
+        let scroll = parse_remap("scroll_repeat = { axis = 'Vertical', amount = 2, interval = '100ms' }");
+
+        fire_remap(get_pad(0), "test", scroll, true).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        fire_remap(get_pad(0), "test", scroll, false).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(take_outputs(), vec![Output::Scroll(2, enigo::Axis::Vertical); 3]);
+
+        // The amount ramps up like the mouse speed, keeping the fractions of ticks
+        let ramp = "scroll_initial_speed = 0.5\nscroll_max_speed = 2\nscroll_ticks_to_reach_max_speed = 3";
+        assert!(reload_config(ramp).await);
+        fire_remap(get_pad(0), "test", scroll, true).await;
+        tokio::time::sleep(Duration::from_millis(450)).await;
+        fire_remap(get_pad(0), "test", scroll, false).await;
+        let scrolls = take_outputs()
+            .into_iter()
+            .map(|output| match output {
+                Output::Scroll(ticks, enigo::Axis::Vertical) => ticks,
+                output => panic!("Unexpected output: {output:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(scrolls, [1, 2, 3, 4, 4]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_turbo() {
+        let _config = ConfigGuard::new("").await;
+        let turbo = parse_remap("turbo = { inner = { seq = ['Space'] }, interval = '100ms' }");
+
+        fire_remap(get_pad(0), "test", turbo, true).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        fire_remap(get_pad(0), "test", turbo, false).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let tap = [
+            Output::Key(enigo::Key::Space, enigo::Direction::Press),
+            Output::Key(enigo::Key::Space, enigo::Direction::Release),
+        ];
+        assert_eq!(take_outputs(), [tap.clone(), tap.clone(), tap].concat());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_macro() {
+        let _config = ConfigGuard::new("").await;
+        let combo = parse_remap(
+            "macro = [{ press = 'Shift' }, { delay = '20ms' }, { press = 'Tab' }, { delay = '20ms' }, \
+             { release = 'Tab' }, { release = 'Shift' }]",
+        );
+
+        fire_remap(get_pad(0), "test", combo, true).await;
+        fire_remap(get_pad(0), "test", combo, false).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::Key(enigo::Key::Shift, enigo::Direction::Press)]
+        );
+
+        // The inputs of the sticks are injected while the macro waits
+        get_input_sender().send([InputCommand::MoveMouse(1, 0, enigo::Coordinate::Rel)]);
+        assert_eq!(take_outputs(), [Output::MoveMouse(1, 0, enigo::Coordinate::Rel)]);
+
+        // Pressing again while the macro plays is ignored by default
+        fire_remap(get_pad(0), "test", combo, true).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Key(enigo::Key::Tab, enigo::Direction::Press),
+                Output::Key(enigo::Key::Tab, enigo::Direction::Release),
+                Output::Key(enigo::Key::Shift, enigo::Direction::Release)
+            ]
+        );
+
+        // Or starts over, releasing the held keys
+        assert!(reload_config("macro_cancel_on_press = true").await);
+        fire_remap(get_pad(0), "test", combo, true).await;
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        fire_remap(get_pad(0), "test", combo, true).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let outputs = take_outputs();
+        assert_eq!(
+            outputs[..5],
+            [
+                Output::Key(enigo::Key::Shift, enigo::Direction::Press),
+                Output::Key(enigo::Key::Tab, enigo::Direction::Press),
+                Output::Key(enigo::Key::Tab, enigo::Direction::Release),
+                Output::Key(enigo::Key::Shift, enigo::Direction::Release),
+                Output::Key(enigo::Key::Shift, enigo::Direction::Press)
+            ]
+        );
+        assert_eq!(outputs.len(), 8);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_macros_per_input() {
+        let _config = ConfigGuard::new("").await;
+        let hold = |key| {
+            parse_remap(&format!("macro = [{{ press = '{key}' }}, {{ delay = '50ms' }}, {{ release = '{key}' }}]"))
+        };
+        let (shift, tab) = (hold("Shift"), hold("Tab"));
+        let key = |key, direction| Output::Key(key, direction);
+
+        // The macros of different inputs and controllers play at the same time
+        fire_remap(get_pad(82), "south", shift, true).await;
+        fire_remap(get_pad(82), "east", tab, true).await;
+        fire_remap(get_pad(83), "south", tab, true).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                key(enigo::Key::Shift, Direction::Press),
+                key(enigo::Key::Tab, Direction::Press),
+                key(enigo::Key::Tab, Direction::Press)
+            ]
+        );
+
+        // Resetting a controller stops its macros, releasing their keys, and leaves the others playing
+        reset_controller_state(get_pad(82)).await;
+        let mut outputs = take_outputs();
+        outputs.sort_by_key(|output| format!("{output:?}"));
+        assert_eq!(outputs, [key(enigo::Key::Shift, Direction::Release), key(enigo::Key::Tab, Direction::Release)]);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(take_outputs(), [key(enigo::Key::Tab, Direction::Release)]);
+        assert!(get_pad(82).running_macros.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_text() {
+        let _config = ConfigGuard::new("").await;
+        let text = parse_remap("text = 'Olá, ça va? ✓'");
+
+        fire_remap(get_pad(0), "test", text, true).await;
+        fire_remap(get_pad(0), "test", text, false).await;
+        assert_eq!(take_outputs(), [Output::Text("Olá, ça va? ✓".to_string())]);
+    }
+
+    #[test]
+    fn test_analog_rate() {
+        assert_eq!(analog_rate(None, 0.5, 2., 10., 1.), 10.);
+        assert_eq!(analog_rate(Some(0.5), 0.5, 2., 10., 1.), 2.);
+        assert_eq!(analog_rate(Some(0.75), 0.5, 2., 10., 1.), 6.);
+        assert_eq!(analog_rate(Some(0.75), 0.5, 2., 10., 2.), 4.);
+        assert_eq!(analog_rate(Some(1.), 0.5, 2., 10., 2.), 10.);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_analog() {
+        let _config = ConfigGuard::new(
+            r#"
+            trigger_activation_threshold = 0.2
+            [main]
+            left_trigger = { analog = { inner = { mouse_click = 'Left' }, min_rate = 2, max_rate = 10 } }
+            "#,
+        )
+        .await;
+        let pad = get_pad(64);
+        let click = Output::Button(enigo::Button::Left, enigo::Direction::Click);
+
+        // A full pull clicks 10 times per second, until let go
+        handle_trigger_changed(pad, Trigger::LeftTrigger, 1.).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        handle_trigger_changed(pad, Trigger::LeftTrigger, 0.).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(take_outputs(), vec![click.clone(); 3]);
+
+        // A light pull only twice per second
+        handle_trigger_changed(pad, Trigger::LeftTrigger, 0.2).await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        handle_trigger_changed(pad, Trigger::LeftTrigger, 0.).await;
+        assert_eq!(take_outputs(), vec![click; 3]);
+    }
+
+    #[tokio::test]
+    async fn test_toggle() {
+        let _config = ConfigGuard::new("").await;
+        let pad = get_pad(30);
+        let toggle = parse_remap("toggle = ['Shift', 'Control']");
+
+        // The first press latches the keys, the next one releases them
+        fire_remap(pad, "west", toggle, true).await;
+        fire_remap(pad, "west", toggle, false).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Key(enigo::Key::Shift, Direction::Press),
+                Output::Key(enigo::Key::Control, Direction::Press)
+            ]
+        );
+        fire_remap(pad, "west", toggle, true).await;
+        fire_remap(pad, "west", toggle, false).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Key(enigo::Key::Control, Direction::Release),
+                Output::Key(enigo::Key::Shift, Direction::Release)
+            ]
+        );
+
+        // A disconnect releases latched keys
+        fire_remap(pad, "west", toggle, true).await;
+        take_outputs();
+        handle_disconnected(pad, Duration::ZERO).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Key(enigo::Key::Control, Direction::Release),
+                Output::Key(enigo::Key::Shift, Direction::Release)
+            ]
+        );
+        assert!(pad.latched_toggles.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_releases_held_inputs() {
+        let _config = ConfigGuard::new("[main]\nsouth = { sync = ['Shift'] }\neast = { mouse = 'Left' }").await;
+        let pad = get_pad(31);
+
+        press_input(pad, "south", true).await;
+        press_input(pad, "east", true).await;
+        take_outputs();
+
+        // Keys and mouse buttons stay down in the OS until they are released
+        handle_disconnected(pad, Duration::ZERO).await;
+        let outputs = take_outputs();
+        assert_eq!(outputs.len(), 2);
+        assert!(outputs.contains(&Output::Key(enigo::Key::Shift, Direction::Release)));
+        assert!(outputs.contains(&Output::Button(enigo::Button::Left, Direction::Release)));
+        assert!(pad.held_inputs.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_toggled_activators() {
+        let _config = ConfigGuard::new(
+            r#"
+            alternative_activator = 'select'
+            alternative_activator_mode = 'toggle'
+            [layer_activators]
+            fn = 'left_bumper'
+            nav = 'right_bumper'
+            [layer_activator_modes]
+            fn = 'toggle'
+            [layers.fn]
+            [layers.nav]
+            "#,
+        )
+        .await;
+        let pad = get_pad(32);
+
+        // A toggled activator latches its set until pressed again
+        press_input(pad, "select", true).await;
+        press_input(pad, "select", false).await;
+        assert!(pad.is_alternative_active());
+        press_input(pad, "left_bumper", true).await;
+        press_input(pad, "left_bumper", false).await;
+        press_input(pad, "right_bumper", true).await;
+        assert_eq!(*pad.active_layers.lock().unwrap(), ["fn", "nav"]);
+        assert_eq!(pad.active_layer(), "nav");
+
+        // A disconnect only drops the held ones, and a reconnect the toggled ones
+        handle_disconnected(pad, Duration::ZERO).await;
+        assert!(pad.is_alternative_active());
+        assert_eq!(*pad.active_layers.lock().unwrap(), ["fn"]);
+        handle_connected(pad, None).await;
+        assert!(!pad.is_alternative_active());
+        assert!(pad.active_layers.lock().unwrap().is_empty());
+        assert_eq!(pad.active_layer(), "main");
+
+        press_input(pad, "select", true).await;
+        press_input(pad, "select", false).await;
+        press_input(pad, "select", true).await;
+        assert!(!pad.is_alternative_active());
+
+        reset_controller_state(pad).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_button_debounce() {
+        let _config = ConfigGuard::new("button_debounce = '50ms'\n[main]\nsouth = { sync = ['Tab'] }").await;
+        let pad = get_pad(73);
+        let tab = |direction| Output::Key(enigo::Key::Tab, direction);
+
+        // A release bouncing back to a press within the window is ignored
+        press_input(pad, "south", true).await;
+        press_input(pad, "south", false).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        press_input(pad, "south", true).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(take_outputs(), [tab(Direction::Press)]);
+        assert!(pad.held_inputs.lock().unwrap().contains("south"));
+
+        // A lasting release fires once the window passes
+        press_input(pad, "south", false).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(take_outputs(), []);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(take_outputs(), [tab(Direction::Release)]);
+
+        // A press after the window is a new press
+        press_input(pad, "south", true).await;
+        assert_eq!(take_outputs(), [tab(Direction::Press)]);
+        reset_controller_state(pad).await;
+        assert_eq!(take_outputs(), [tab(Direction::Release)]);
+    }
+
+    #[tokio::test]
+    async fn test_chord_bindings() {
+        let config = r#"
+            [main]
+            south = { sync = ['Space'] }
+            'start+south' = { sync = ['Tab'] }
+            'start+south+east' = { sync = ['Return'] }
+        "#;
+        let _config = ConfigGuard::new(config).await;
+        let pad = get_pad(72);
+        let key = |key, direction| Output::Key(key, direction);
+
+        // The chord replaces the binding of the input completing it
+        press_input(pad, "start", true).await;
+        press_input(pad, "south", true).await;
+        press_input(pad, "south", false).await;
+        press_input(pad, "start", false).await;
+        assert_eq!(
+            take_outputs(),
+            [key(enigo::Key::Tab, Direction::Press), key(enigo::Key::Tab, Direction::Release)]
+        );
+
+        // An input pressed first is released by the chord, which ends with any of its inputs
+        press_input(pad, "south", true).await;
+        press_input(pad, "start", true).await;
+        press_input(pad, "start", false).await;
+        press_input(pad, "south", false).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                key(enigo::Key::Space, Direction::Press),
+                key(enigo::Key::Space, Direction::Release),
+                key(enigo::Key::Tab, Direction::Press),
+                key(enigo::Key::Tab, Direction::Release)
+            ]
+        );
+
+        // The largest chord held wins
+        press_input(pad, "start", true).await;
+        press_input(pad, "east", true).await;
+        press_input(pad, "south", true).await;
+        press_input(pad, "east", false).await;
+        press_input(pad, "south", false).await;
+        press_input(pad, "start", false).await;
+        assert_eq!(
+            take_outputs(),
+            [key(enigo::Key::Return, Direction::Press), key(enigo::Key::Return, Direction::Release)]
+        );
+
+        // Alone, the input keeps its own binding
+        press_input(pad, "south", true).await;
+        press_input(pad, "south", false).await;
+        assert_eq!(
+            take_outputs(),
+            [key(enigo::Key::Space, Direction::Press), key(enigo::Key::Space, Direction::Release)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chord_activator() {
+        let _config = ConfigGuard::new(
+            r#"
+            chord_activator = ['left_bumper', 'right_bumper']
+            [main]
+            left_bumper = { sync = ['Shift'] }
+            south = { seq = ['Space'] }
+            [alt]
+            south = { seq = ['Return'] }
+            "#,
+        )
+        .await;
+        let pad = get_pad(33);
+
+        // The chord inputs keep their own bindings
+        press_input(pad, "left_bumper", true).await;
+        assert!(!pad.is_alternative_active());
+        press_input(pad, "right_bumper", true).await;
+        assert!(pad.is_alternative_active());
+        press_input(pad, "south", true).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Key(enigo::Key::Shift, Direction::Press),
+                Output::Key(enigo::Key::Return, Direction::Press),
+                Output::Key(enigo::Key::Return, Direction::Release)
+            ]
+        );
+
+        // Releasing any of them ends the chord
+        press_input(pad, "south", false).await;
+        press_input(pad, "right_bumper", false).await;
+        assert!(!pad.is_alternative_active());
+        press_input(pad, "left_bumper", false).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::Key(enigo::Key::Shift, Direction::Release)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socd_resolution() {
+        let _config = ConfigGuard::new("").await;
+        let pad = get_pad(34);
+        let key = |key, direction| Output::Key(key, direction);
+        let (left, right) = (enigo::Key::LeftArrow, enigo::Key::RightArrow);
+        let config = |resolution| {
+            format!(
+                "socd_resolution = '{resolution}'\n[main]\ndpad_left = {{ sync = ['LeftArrow'] }}\ndpad_right = {{ sync = ['RightArrow'] }}"
+            )
+        };
+
+        assert!(reload_config(&config("last_wins")).await);
+        press_input(pad, "dpad_left", true).await;
+        press_input(pad, "dpad_right", true).await;
+        press_input(pad, "dpad_right", false).await;
+        press_input(pad, "dpad_left", false).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                key(left, Direction::Press),
+                key(left, Direction::Release),
+                key(right, Direction::Press),
+                key(right, Direction::Release),
+                key(left, Direction::Press),
+                key(left, Direction::Release),
+            ]
+        );
+
+        assert!(reload_config(&config("neutral")).await);
+        press_input(pad, "dpad_left", true).await;
+        press_input(pad, "dpad_right", true).await;
+        press_input(pad, "dpad_left", false).await;
+        press_input(pad, "dpad_right", false).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                key(left, Direction::Press),
+                key(left, Direction::Release),
+                key(right, Direction::Press),
+                key(right, Direction::Release),
+            ]
+        );
+
+        assert!(reload_config(&config("first_wins")).await);
+        press_input(pad, "dpad_left", true).await;
+        press_input(pad, "dpad_right", true).await;
+        press_input(pad, "dpad_right", false).await;
+        press_input(pad, "dpad_left", false).await;
+        assert_eq!(
+            take_outputs(),
+            [key(left, Direction::Press), key(left, Direction::Release)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_double_tap() {
+        let _config = ConfigGuard::new("").await;
+        let pad = get_pad(40);
+        let double_tap = parse_remap("double_tap = { single = { sync = ['Space'] }, double = { sync = ['Return'] } }");
+        let window = get_config().double_tap_window;
+        let press = |key| Output::Key(key, Direction::Press);
+        let release = |key| Output::Key(key, Direction::Release);
+
+        // A single tap fires once the window is over
+        fire_remap(pad, "north", double_tap, true).await;
+        fire_remap(pad, "north", double_tap, false).await;
+        assert!(take_outputs().is_empty());
+        tokio::time::sleep(window * 2).await;
+        assert_eq!(
+            take_outputs(),
+            [press(enigo::Key::Space), release(enigo::Key::Space)]
+        );
+
+        // A second press within the window fires the double action only
+        fire_remap(pad, "north", double_tap, true).await;
+        fire_remap(pad, "north", double_tap, false).await;
+        tokio::time::sleep(window / 2).await;
+        fire_remap(pad, "north", double_tap, true).await;
+        fire_remap(pad, "north", double_tap, false).await;
+        tokio::time::sleep(window * 2).await;
+        assert_eq!(
+            take_outputs(),
+            [press(enigo::Key::Return), release(enigo::Key::Return)]
+        );
+
+        // A long first press is a single press, held until released
+        fire_remap(pad, "north", double_tap, true).await;
+        tokio::time::sleep(window * 2).await;
+        assert_eq!(take_outputs(), [press(enigo::Key::Space)]);
+        fire_remap(pad, "north", double_tap, false).await;
+        assert_eq!(take_outputs(), [release(enigo::Key::Space)]);
+        assert!(pad.taps.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hold_tap() {
+        let _config = ConfigGuard::new("").await;
+        let pad = get_pad(41);
+        let hold_tap = parse_remap(
+            "hold_tap = { tap = { seq = ['Escape'] }, hold = { sync = ['Control'] }, hold_duration = '200ms' }",
+        );
+
+        // A short tap fires the tap action on release
+        fire_remap(pad, "left_bumper", hold_tap, true).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(take_outputs().is_empty());
+        fire_remap(pad, "left_bumper", hold_tap, false).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                Output::Key(enigo::Key::Escape, Direction::Press),
+                Output::Key(enigo::Key::Escape, Direction::Release)
+            ]
+        );
+
+        // Holding past the threshold fires the hold action instead of the tap
+        fire_remap(pad, "left_bumper", hold_tap, true).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::Key(enigo::Key::Control, Direction::Press)]
+        );
+        fire_remap(pad, "left_bumper", hold_tap, false).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::Key(enigo::Key::Control, Direction::Release)]
+        );
+        assert!(pad.taps.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delayed() {
+        let _config = ConfigGuard::new("").await;
+        let pad = get_pad(41);
+        let delayed = parse_remap("delayed = { inner = { sync = ['Escape'] }, delay = '500ms' }");
+        let escape = |direction| Output::Key(enigo::Key::Escape, direction);
+
+        // Releasing before the delay fires nothing
+        fire_remap(pad, "start", delayed, true).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        fire_remap(pad, "start", delayed, false).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(take_outputs().is_empty());
+
+        // Holding past it presses the remap until the release
+        fire_remap(pad, "start", delayed, true).await;
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(take_outputs(), [escape(Direction::Press)]);
+        fire_remap(pad, "start", delayed, false).await;
+        assert_eq!(take_outputs(), [escape(Direction::Release)]);
+        assert!(pad.taps.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_block() {
+        let config = r#"
+            alternative_activator = 'left_bumper'
+            alt_inherits_main = true
+            [main]
+            south = { sync = ['Space'] }
+            east = { sync = ['Escape'] }
+            [alt]
+            east = { block = {} }
+        "#;
+        let _config = ConfigGuard::new(config).await;
+        let pad = get_pad(76);
+        let space = |direction| Output::Key(enigo::Key::Space, direction);
+
+        // The alt set inherits south, but masks east
+        press_input(pad, "left_bumper", true).await;
+        for input in ["east", "south"] {
+            press_input(pad, input, true).await;
+            press_input(pad, input, false).await;
+        }
+        press_input(pad, "left_bumper", false).await;
+        assert_eq!(take_outputs(), [space(Direction::Press), space(Direction::Release)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_repeat_delays() {
+        let _config = ConfigGuard::new("").await;
+        let repeat = parse_remap("repeat = { key = 'Return', initial_delay = '100ms', sub_delay = '10ms' }");
+
+        // Clicks at 0, 100 and 110ms
+        fire_remap(get_pad(0), "test", repeat, true).await;
+        tokio::time::sleep(Duration::from_millis(115)).await;
+        fire_remap(get_pad(0), "test", repeat, false).await;
+        assert_eq!(
+            take_outputs(),
+            vec![Output::Key(enigo::Key::Return, Direction::Click); 3]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_repeat_acceleration() {
+        let _config = ConfigGuard::new("").await;
+        let repeat = parse_remap(
+            "repeat = { key = 'Return', initial_delay = '100ms', sub_delay = '40ms', sub_delay_min = '10ms', \
+             acceleration = 0.5 }",
+        );
+
+        // Clicks at 0, 100, 140, 160, 170 and 180ms
+        fire_remap(get_pad(0), "test", repeat, true).await;
+        tokio::time::sleep(Duration::from_millis(185)).await;
+        fire_remap(get_pad(0), "test", repeat, false).await;
+        assert_eq!(
+            take_outputs(),
+            vec![Output::Key(enigo::Key::Return, Direction::Click); 6]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_simultaneous_repeats() {
+        let _config = ConfigGuard::new(
+            r#"
+            [main]
+            south = { repeat = { key = 'Tab', initial_delay = '100ms', sub_delay = '100ms' } }
+            east = { repeat = { key = 'Space', initial_delay = '100ms', sub_delay = '100ms' } }
+            west = { turbo = { inner = { seq = ['Escape'] }, interval = '100ms' } }
+            north = { turbo = { inner = { seq = ['Return'] }, interval = '100ms' } }
+            "#,
+        )
+        .await;
+        let pad = get_pad(66);
+        let click = |key| Output::Key(key, Direction::Click);
+
+        // Each held repeat keeps going until its own release
+        press_input(pad, "south", true).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        press_input(pad, "east", true).await;
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        press_input(pad, "south", false).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        press_input(pad, "east", false).await;
+        assert_eq!(
+            take_outputs(),
+            [
+                click(enigo::Key::Tab),
+                click(enigo::Key::Space),
+                click(enigo::Key::Tab),
+                click(enigo::Key::Space),
+                click(enigo::Key::Space)
+            ]
+        );
+
+        // And so do turbos
+        press_input(pad, "west", true).await;
+        press_input(pad, "north", true).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        press_input(pad, "west", false).await;
+        press_input(pad, "north", false).await;
+        let outputs = take_outputs();
+        for key in [enigo::Key::Escape, enigo::Key::Return] {
+            let count = outputs.iter().filter(|output| **output == Output::Key(key, Direction::Press)).count();
+            assert_eq!(count, 2, "{key:?}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_repeat_stops_after_layer_switch() {
+        let _config = ConfigGuard::new(
+            r#"
+            alternative_activator = 'select'
+            [main]
+            south = { repeat = 'Tab' }
+            [alt]
+            south = { seq = ['Space'] }
+            "#,
+        )
+        .await;
+        let pad = get_pad(65);
+
+        // The release resolves to the `alt` binding, yet stops the repeat of the `main` one
+        press_input(pad, "south", true).await;
+        press_input(pad, "select", true).await;
+        press_input(pad, "south", false).await;
+        tokio::time::sleep(get_config().key_repeat_initial_delay * 2).await;
+        assert_eq!(
+            take_outputs(),
+            [Output::Key(enigo::Key::Tab, enigo::Direction::Click)]
+        );
+
+        press_input(pad, "select", false).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_nudge_repeat() {
+        let _config = ConfigGuard::new("").await;
+        let nudge = parse_remap("nudge = { dx = -1, dy = 0, repeat = true }");
+        let delay = get_config().key_repeat_initial_delay + get_config().key_repeat_sub_delay / 2;
+
+        fire_remap(get_pad(0), "test", nudge, true).await;
+        tokio::time::sleep(delay).await;
+        fire_remap(get_pad(0), "test", nudge, false).await;
+        tokio::time::sleep(delay).await;
+        assert_eq!(
+            take_outputs(),
+            vec![Output::MoveMouse(-1, 0, enigo::Coordinate::Rel); 2]
+        );
+    }
+
+    #[test]
+    fn test_swap_layers() {
+        let _lock = STATE_LOCK.blocking_lock();
+        let config = toml::from_str::<Config>(
+            r#"
+            [main]
+            south = { seq = ['Space'] }
+            [alt]
+            south = { seq = ['Return'] }
+            "#,
+        )
+        .unwrap();
+        let pad = get_pad(0);
+        let resolve = || match &config.get_binding("south", pad.is_alternative_active(), None).unwrap().remap {
+            Remap::Seq(seq) => seq[0],
+            _ => unreachable!(),
+        };
+
+        pad.is_alternative_active.store(false, Ordering::Relaxed);
+        ARE_LAYERS_SWAPPED.store(true, Ordering::Relaxed);
+        assert_eq!(resolve(), enigo::Key::Return);
+
+        // The activator switches back to main while the layers are swapped
+        pad.is_alternative_active.store(true, Ordering::Relaxed);
+        assert_eq!(resolve(), enigo::Key::Space);
+
+        ARE_LAYERS_SWAPPED.store(false, Ordering::Relaxed);
+        assert_eq!(resolve(), enigo::Key::Return);
+
+        pad.is_alternative_active.store(false, Ordering::Relaxed);
+        assert_eq!(resolve(), enigo::Key::Space);
+    }
+
+    #[test]
+    fn test_unless_suppresses_press_and_release() {
+        let _lock = STATE_LOCK.blocking_lock();
+        let binding = toml::from_str::<Binding>("seq = ['Space']\nunless = 'test_modifier'").unwrap();
+
+        let pad = get_pad(20);
+
+        pad.held_inputs.lock().unwrap().insert("test_modifier".to_string());
+        assert!(is_binding_suppressed(pad, "test_unless", &binding, true));
+        pad.held_inputs.lock().unwrap().remove("test_modifier");
+        assert!(is_binding_suppressed(pad, "test_unless", &binding, false));
+
+        assert!(!is_binding_suppressed(pad, "test_unless", &binding, true));
+        assert!(!is_binding_suppressed(pad, "test_unless", &binding, false));
+    }
+}
@@ -0,0 +1,274 @@
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+use crate::stick::{Stick, StickDirection};
+
+/// Controller buttons that can be remapped, paired with their input names.
+pub const BUTTON_INPUT_NAMES: [(gilrs::Button, &str); 17] = [
+    (gilrs::Button::North, "north"),
+    (gilrs::Button::South, "south"),
+    (gilrs::Button::West, "west"),
+    (gilrs::Button::East, "east"),
+    (gilrs::Button::LeftTrigger, "left_bumper"),
+    (gilrs::Button::RightTrigger, "right_bumper"),
+    (gilrs::Button::LeftTrigger2, "left_trigger"),
+    (gilrs::Button::RightTrigger2, "right_trigger"),
+    (gilrs::Button::Select, "select"),
+    (gilrs::Button::Start, "start"),
+    (gilrs::Button::Mode, "mode"),
+    (gilrs::Button::LeftThumb, "left_thumb"),
+    (gilrs::Button::RightThumb, "right_thumb"),
+    (gilrs::Button::DPadUp, "dpad_up"),
+    (gilrs::Button::DPadDown, "dpad_down"),
+    (gilrs::Button::DPadLeft, "dpad_left"),
+    (gilrs::Button::DPadRight, "dpad_right"),
+];
+
+/// Prefix of the input names of the buttons without a name of their own, followed by their raw code.
+const UNNAMED_BUTTON_PREFIX: &str = "button_";
+
+/// Returns the input name of a controller button.
+///
+/// Buttons without a name of their own, e.g. the paddles of some controllers, are
+/// named after the raw code the driver reports for them, e.g. `button_704`.
+pub fn get_button_input_name(button: gilrs::Button, code: u32) -> String {
+    BUTTON_INPUT_NAMES
+        .iter()
+        .find(|(candidate, _)| *candidate == button)
+        .map_or_else(|| format!("{UNNAMED_BUTTON_PREFIX}{code}"), |(_, name)| name.to_string())
+}
+
+/// Checks whether an input name belongs to a controller button.
+pub fn is_button_input_name(input_name: &str) -> bool {
+    BUTTON_INPUT_NAMES.iter().any(|(_, name)| *name == input_name) || is_unnamed_button_input_name(input_name)
+}
+
+/// Checks whether an input name is the one of a button named after its raw code.
+fn is_unnamed_button_input_name(input_name: &str) -> bool {
+    input_name
+        .strip_prefix(UNNAMED_BUTTON_PREFIX)
+        .is_some_and(|code| code.parse::<u32>().is_ok_and(|parsed| parsed.to_string() == code))
+}
+
+/// Checks whether a name is an input name, listed by `input_names` or of a button named after its code.
+pub fn is_input_name(name: &str) -> bool {
+    input_names().contains(&name) || is_unnamed_button_input_name(name)
+}
+
+/// Returns the inputs of a chord, written as input names joined by `+`, e.g. `start+south`.
+pub fn chord_inputs(name: &str) -> Option<Vec<&str>> {
+    name.contains('+').then(|| name.split('+').collect())
+}
+
+/// Checks whether a name can be remapped: an input name, or a chord of distinct input names.
+pub fn is_remappable_name(name: &str) -> bool {
+    match chord_inputs(name) {
+        Some(inputs) => inputs
+            .iter()
+            .enumerate()
+            .all(|(i, input)| is_input_name(input) && !inputs[..i].contains(input)),
+        None => is_input_name(name),
+    }
+}
+
+/// Returns the input pointing the other way, for the dpad and stick directions.
+pub fn opposite_input_name(input_name: &str) -> Option<&'static str> {
+    const DPAD_OPPOSITES: [(&str, &str); 4] = [
+        ("dpad_up", "dpad_down"),
+        ("dpad_down", "dpad_up"),
+        ("dpad_left", "dpad_right"),
+        ("dpad_right", "dpad_left"),
+    ];
+
+    DPAD_OPPOSITES
+        .iter()
+        .find(|(name, _)| *name == input_name)
+        .map(|(_, opposite)| *opposite)
+        .or_else(|| {
+            [Stick::Left, Stick::Right].into_iter().find_map(|stick| {
+                StickDirection::ALL
+                    .iter()
+                    .find(|direction| direction.input_name(stick) == input_name)
+                    .map(|direction| direction.opposite().input_name(stick))
+            })
+        })
+}
+
+/// Returns every input name that can appear in a mapping set, besides the ones of buttons named after their code.
+pub fn input_names() -> Vec<&'static str> {
+    BUTTON_INPUT_NAMES
+        .iter()
+        .map(|(_, name)| *name)
+        .chain(
+            [Stick::Left, Stick::Right].into_iter().flat_map(|stick| {
+                StickDirection::ALL
+                    .iter()
+                    .map(move |direction| direction.input_name(stick))
+            }),
+        )
+        .collect()
+}
+
+/// Returns the names accepted for keyboard keys, as known by `enigo::Key`'s deserializer.
+pub fn key_names() -> &'static [&'static str] {
+    variant_names::<enigo::Key>()
+}
+
+/// Returns how a key name is written in a remap, e.g. `'Control'` or `{ Unicode = 'a' }`.
+///
+/// Keys carrying a value are detected by asking the parser which form it accepts.
+pub fn key_usage(name: &str) -> String {
+    [
+        format!("'{name}'"),
+        format!("{{ {name} = 'a' }}"),
+        format!("{{ {name} = 0 }}"),
+    ]
+    .into_iter()
+    .find(|usage| parse_key(usage).is_some())
+    .unwrap_or_else(|| name.to_string())
+}
+
+fn parse_key(usage: &str) -> Option<enigo::Key> {
+    let table = toml::from_str::<toml::Table>(&format!("key = {usage}")).ok()?;
+    table.get("key")?.clone().try_into().ok()
+}
+
+/// Extracts the variant names a serde-derived enum accepts.
+///
+/// Serde hands the list of variant names to `deserialize_enum`, so a
+/// deserializer that only records them keeps this list in sync with the parser.
+pub fn variant_names<T: for<'de> Deserialize<'de>>() -> &'static [&'static str] {
+    declared_names::<T>()
+}
+
+/// Extracts the field names a serde-derived struct accepts, the same way as `variant_names`.
+///
+/// Structs with a flattened field are read as maps, so they have no list of fields.
+pub fn field_names<T: for<'de> Deserialize<'de>>() -> &'static [&'static str] {
+    declared_names::<T>()
+}
+
+fn declared_names<T: for<'de> Deserialize<'de>>() -> &'static [&'static str] {
+    let mut names: &'static [&'static str] = &[];
+    let _ = T::deserialize(DeclaredNames(&mut names));
+    names
+}
+
+struct DeclaredNames<'a>(&'a mut &'static [&'static str]);
+
+#[derive(Debug)]
+struct Unsupported;
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("only enum variant and struct field names can be extracted")
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+impl de::Error for Unsupported {
+    fn custom<T: std::fmt::Display>(_msg: T) -> Self {
+        Unsupported
+    }
+}
+
+impl<'de> Deserializer<'de> for DeclaredNames<'_> {
+    type Error = Unsupported;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(Unsupported)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = variants;
+        Err(Unsupported)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(Unsupported)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_button_input_name_round_trip() {
+        for (button, name) in BUTTON_INPUT_NAMES {
+            assert_eq!(get_button_input_name(button, 0), name);
+            assert!(is_button_input_name(name));
+        }
+    }
+
+    #[test]
+    fn test_unnamed_button_input_name() {
+        assert_eq!(get_button_input_name(gilrs::Button::Unknown, 704), "button_704");
+        assert_eq!(get_button_input_name(gilrs::Button::C, 306), "button_306");
+        assert!(is_button_input_name("button_704"));
+        assert!(is_input_name("button_704"));
+        assert!(is_remappable_name("start+button_704"));
+
+        for name in ["button_", "button_x", "button_-1", "button_0704", "button_704 "] {
+            assert!(!is_input_name(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_opposite_input_name() {
+        assert_eq!(opposite_input_name("dpad_left"), Some("dpad_right"));
+        assert_eq!(opposite_input_name("right_stick_up"), Some("right_stick_down"));
+        assert_eq!(opposite_input_name("left_stick_up_left"), Some("left_stick_down_right"));
+        assert_eq!(opposite_input_name("south"), None);
+
+        for name in input_names() {
+            if let Some(opposite) = opposite_input_name(name) {
+                assert_eq!(opposite_input_name(opposite), Some(name));
+            }
+        }
+    }
+
+    #[test]
+    fn test_chord_names() {
+        assert_eq!(chord_inputs("start+south"), Some(vec!["start", "south"]));
+        assert_eq!(chord_inputs("south"), None);
+
+        assert!(is_remappable_name("south"));
+        assert!(is_remappable_name("start+south"));
+        assert!(is_remappable_name("left_bumper+right_bumper+dpad_up"));
+        assert!(!is_remappable_name("start+souht"));
+        assert!(!is_remappable_name("start+start"));
+        assert!(!is_remappable_name("start+"));
+    }
+
+    #[test]
+    fn test_key_names_are_parseable() {
+        let names = key_names();
+        assert!(names.contains(&"Control"));
+        assert!(names.contains(&"PageUp"));
+
+        for name in names {
+            assert!(parse_key(&key_usage(name)).is_some(), "{name} is not a valid key");
+        }
+        assert_eq!(key_usage("Control"), "'Control'");
+        assert_eq!(key_usage("Unicode"), "{ Unicode = 'a' }");
+        assert_eq!(key_usage("Other"), "{ Other = 0 }");
+    }
+}
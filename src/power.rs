@@ -0,0 +1,55 @@
+/// Checks whether the device currently runs on battery power.
+///
+/// Any power supply of type `Battery` reporting `Discharging` counts as running
+/// on battery.
+#[cfg(target_os = "linux")]
+pub fn is_on_battery() -> bool {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).unwrap_or_default();
+
+    std::fs::read_dir("/sys/class/power_supply")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .any(|supply| {
+            read(supply.join("type")).trim() == "Battery"
+                && read(supply.join("status")).trim() == "Discharging"
+        })
+}
+
+#[cfg(target_os = "windows")]
+#[repr(C)]
+struct SystemPowerStatus {
+    ac_line_status: u8,
+    battery_flag: u8,
+    battery_life_percent: u8,
+    system_status_flag: u8,
+    battery_life_time: u32,
+    battery_full_life_time: u32,
+}
+
+#[cfg(target_os = "windows")]
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+}
+
+/// Checks whether the device currently runs on battery power.
+///
+/// The device is on battery when Windows reports the AC line as offline.
+#[cfg(target_os = "windows")]
+pub fn is_on_battery() -> bool {
+    let mut status = std::mem::MaybeUninit::<SystemPowerStatus>::uninit();
+
+    unsafe {
+        GetSystemPowerStatus(status.as_mut_ptr()) != 0 && status.assume_init().ac_line_status == 0
+    }
+}
+
+/// Checks whether the device currently runs on battery power.
+///
+/// Power detection is not supported on this platform, so this is always `false`.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn is_on_battery() -> bool {
+    false
+}
@@ -0,0 +1,601 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+use crate::config::{AccelerationCurve, DeadZoneShape, DiagonalBias, MouseRounding};
+
+/// One of the two analog sticks of a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+/// A digital direction produced by pushing a stick past its trigger zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Only fired by a stick set to 8 directions, like the other diagonals.
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl StickDirection {
+    /// Every direction, in the order they are listed to users.
+    pub const ALL: [StickDirection; 8] = [
+        StickDirection::Up,
+        StickDirection::Down,
+        StickDirection::Left,
+        StickDirection::Right,
+        StickDirection::UpLeft,
+        StickDirection::UpRight,
+        StickDirection::DownLeft,
+        StickDirection::DownRight,
+    ];
+
+    /// The directions of an 8-way stick, counterclockwise from `Right`.
+    const COUNTERCLOCKWISE: [StickDirection; 8] = [
+        StickDirection::Right,
+        StickDirection::UpRight,
+        StickDirection::Up,
+        StickDirection::UpLeft,
+        StickDirection::Left,
+        StickDirection::DownLeft,
+        StickDirection::Down,
+        StickDirection::DownRight,
+    ];
+
+    /// Returns the direction pointing the other way.
+    pub fn opposite(self) -> StickDirection {
+        match self {
+            StickDirection::Up => StickDirection::Down,
+            StickDirection::Down => StickDirection::Up,
+            StickDirection::Left => StickDirection::Right,
+            StickDirection::Right => StickDirection::Left,
+            StickDirection::UpLeft => StickDirection::DownRight,
+            StickDirection::UpRight => StickDirection::DownLeft,
+            StickDirection::DownLeft => StickDirection::UpRight,
+            StickDirection::DownRight => StickDirection::UpLeft,
+        }
+    }
+
+    /// Returns the input name used to remap this direction on a stick.
+    pub fn input_name(self, stick: Stick) -> &'static str {
+        match (stick, self) {
+            (Stick::Left, StickDirection::Up) => "left_stick_up",
+            (Stick::Left, StickDirection::Down) => "left_stick_down",
+            (Stick::Left, StickDirection::Left) => "left_stick_left",
+            (Stick::Left, StickDirection::Right) => "left_stick_right",
+            (Stick::Left, StickDirection::UpLeft) => "left_stick_up_left",
+            (Stick::Left, StickDirection::UpRight) => "left_stick_up_right",
+            (Stick::Left, StickDirection::DownLeft) => "left_stick_down_left",
+            (Stick::Left, StickDirection::DownRight) => "left_stick_down_right",
+            (Stick::Right, StickDirection::Up) => "right_stick_up",
+            (Stick::Right, StickDirection::Down) => "right_stick_down",
+            (Stick::Right, StickDirection::Left) => "right_stick_left",
+            (Stick::Right, StickDirection::Right) => "right_stick_right",
+            (Stick::Right, StickDirection::UpLeft) => "right_stick_up_left",
+            (Stick::Right, StickDirection::UpRight) => "right_stick_up_right",
+            (Stick::Right, StickDirection::DownLeft) => "right_stick_down_left",
+            (Stick::Right, StickDirection::DownRight) => "right_stick_down_right",
+        }
+    }
+}
+
+/// Resolves a stick angle to one of four cardinal directions.
+///
+/// The circle is split into four quarter sectors centered on the cardinal
+/// directions, so every angle maps to a direction. An angle lying exactly on a
+/// diagonal is given to the vertical or horizontal neighbor according to `bias`.
+///
+/// # Arguments
+///
+/// * `angle` - The stick angle in radians, as returned by `y.atan2(x)`.
+/// * `bias` - Which neighbor wins on a sector boundary.
+pub fn direction_4way(angle: f32, bias: DiagonalBias) -> StickDirection {
+    let abs_angle = angle.abs();
+    let is_vertical = match bias {
+        DiagonalBias::Vertical => (FRAC_PI_4..=3. * FRAC_PI_4).contains(&abs_angle),
+        DiagonalBias::Horizontal => abs_angle > FRAC_PI_4 && abs_angle < 3. * FRAC_PI_4,
+    };
+
+    if is_vertical {
+        if angle > 0. {
+            StickDirection::Up
+        } else {
+            StickDirection::Down
+        }
+    } else if abs_angle < FRAC_PI_2 {
+        StickDirection::Right
+    } else {
+        StickDirection::Left
+    }
+}
+
+/// Resolves a stick angle to one of eight directions, diagonals included.
+///
+/// The circle is split into eight sectors of 45° centered on each direction.
+///
+/// # Arguments
+///
+/// * `angle` - The stick angle in radians, as returned by `y.atan2(x)`.
+pub fn direction_8way(angle: f32) -> StickDirection {
+    let sector = (angle / FRAC_PI_4).round().rem_euclid(8.) as usize;
+    StickDirection::COUNTERCLOCKWISE[sector]
+}
+
+/// How a stick in directional mode turns its position into directions.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalSettings {
+    /// Distance from the center within which a held direction is always released.
+    pub dead_zone: f32,
+    /// Distance from the center at which a direction is pressed.
+    pub trigger_zone: f32,
+    /// How far below the trigger zone a held direction must fall to be released.
+    pub hysteresis: f32,
+    /// Number of directions, 4 or 8.
+    pub directions: u8,
+    /// Which neighbor wins on a sector boundary, with 4 directions.
+    pub diagonal_bias: DiagonalBias,
+}
+
+/// Resolves a stick position to the direction it is pushed in, whatever its distance to the center.
+pub fn direction(x: f32, y: f32, settings: &DirectionalSettings) -> StickDirection {
+    if settings.directions == 8 {
+        direction_8way(y.atan2(x))
+    } else {
+        direction_4way(y.atan2(x), settings.diagonal_bias)
+    }
+}
+
+/// Decides which direction a stick in directional mode holds at a position, if any.
+///
+/// # Arguments
+///
+/// * `x`, `y` - The stick position.
+/// * `is_held` - Whether the stick held a direction before moving there.
+/// * `settings` - The zones and directions of the stick.
+///
+/// # Returns
+///
+/// The direction the stick points to once past the trigger zone, and as long
+/// as it is held, until it falls below the hysteresis or into the dead zone.
+pub fn pushed_direction(x: f32, y: f32, is_held: bool, settings: &DirectionalSettings) -> Option<StickDirection> {
+    let distance_to_origin = (x * x + y * y).sqrt();
+    let is_pushed = if is_held {
+        distance_to_origin > settings.dead_zone && distance_to_origin > settings.trigger_zone - settings.hysteresis
+    } else {
+        distance_to_origin >= settings.trigger_zone
+    };
+
+    is_pushed.then(|| direction(x, y, settings))
+}
+
+/// Removes the dead zone from a stick position.
+///
+/// # Arguments
+///
+/// * `x`, `y` - The stick position.
+/// * `dead_zone` - The dead zone size.
+/// * `shape` - How the dead zone is applied.
+///
+/// # Returns
+///
+/// The position with the dead zone removed, `(0, 0)` inside of it.
+pub fn apply_dead_zone(x: f32, y: f32, dead_zone: f32, shape: DeadZoneShape) -> (f32, f32) {
+    let shrink_axis = |value: f32| value.signum() * (value.abs() - dead_zone).max(0.);
+    let distance_to_origin = (x * x + y * y).sqrt();
+    // Also keeps the ratio below from dividing by zero at the center
+    if distance_to_origin <= dead_zone {
+        return (0., 0.);
+    }
+    let dead_zone_shrink_ratio = 1. - dead_zone / distance_to_origin;
+
+    match shape {
+        DeadZoneShape::Radial => (x * dead_zone_shrink_ratio, y * dead_zone_shrink_ratio),
+        DeadZoneShape::Axial => (shrink_axis(x), shrink_axis(y)),
+        DeadZoneShape::Scaled => {
+            let scale = dead_zone_shrink_ratio / (1. - dead_zone);
+            (x * scale, y * scale)
+        }
+    }
+}
+
+/// Zeroes a stick position inside the dead zone, leaving it as is outside of it.
+///
+/// Unlike `apply_dead_zone`, the movement does not start from zero at the edge
+/// of the dead zone, but from the actual push.
+///
+/// # Arguments
+///
+/// * `x`, `y` - The stick position.
+/// * `dead_zone` - The dead zone size.
+/// * `shape` - The shape of the dead zone, per axis if `Axial`; `Scaled` is gated like `Radial`.
+///
+/// # Returns
+///
+/// The position, `(0, 0)` inside of the dead zone.
+pub fn gate_dead_zone(x: f32, y: f32, dead_zone: f32, shape: DeadZoneShape) -> (f32, f32) {
+    let gate_axis = |value: f32| if value.abs() > dead_zone { value } else { 0. };
+    if (x * x + y * y).sqrt() <= dead_zone {
+        return (0., 0.);
+    }
+
+    match shape {
+        DeadZoneShape::Radial | DeadZoneShape::Scaled => (x, y),
+        DeadZoneShape::Axial => (gate_axis(x), gate_axis(y)),
+    }
+}
+
+/// Reshapes how far a stick position is from the center, keeping its direction.
+///
+/// The distance is raised to `exponent`: above `1`, small pushes move slower
+/// for precise aiming while full pushes keep their speed; `1` leaves the
+/// position unchanged.
+///
+/// # Arguments
+///
+/// * `x`, `y` - The stick position, with the dead zone removed.
+/// * `exponent` - The exponent of the curve.
+pub fn apply_response_curve(x: f32, y: f32, exponent: f32) -> (f32, f32) {
+    let distance_to_origin = (x * x + y * y).sqrt();
+    if distance_to_origin == 0. {
+        return (0., 0.);
+    }
+
+    let scale = distance_to_origin.powf(exponent) / distance_to_origin;
+    (x * scale, y * scale)
+}
+
+/// Brings a stick position pushed past the unit circle back onto it, keeping its direction.
+///
+/// Sticks with a square gate report up to `sqrt(2)` on the diagonals, which
+/// would otherwise move faster there than straight.
+pub fn normalize_diagonal(x: f32, y: f32) -> (f32, f32) {
+    let distance_to_origin = (x * x + y * y).sqrt();
+    if distance_to_origin <= 1. {
+        return (x, y);
+    }
+
+    (x / distance_to_origin, y / distance_to_origin)
+}
+
+/// Maps a stick position to a position on a display, the stick center being the display center.
+///
+/// # Arguments
+///
+/// * `x`, `y` - The stick position, positive to the right and upwards.
+/// * `width`, `height` - The size of the display in pixels.
+///
+/// # Returns
+///
+/// The position in pixels from the top-left corner, a full push reaching the edge.
+pub fn absolute_position(x: f32, y: f32, width: i32, height: i32) -> (i32, i32) {
+    let half_width = (width - 1) as f32 / 2.;
+    let half_height = (height - 1) as f32 / 2.;
+    (
+        (half_width + x.clamp(-1., 1.) * half_width).round() as i32,
+        (half_height - y.clamp(-1., 1.) * half_height).round() as i32,
+    )
+}
+
+/// Computes the speed of the mouse or the wheel after it has been moving for some ticks.
+///
+/// # Arguments
+///
+/// * `curve` - The shape of the ramp.
+/// * `initial_speed` - The speed on the first tick.
+/// * `max_speed` - The speed reached after `ticks_to_max_speed` ticks.
+/// * `ticks` - The number of ticks since the movement started, e.g. since the stick left its dead zone.
+/// * `ticks_to_max_speed` - The length of the ramp, in ticks.
+pub fn ramp_speed(
+    curve: AccelerationCurve,
+    initial_speed: f32,
+    max_speed: f32,
+    ticks: u32,
+    ticks_to_max_speed: f32,
+) -> f32 {
+    let progress = (ticks as f32 / ticks_to_max_speed).min(1.);
+
+    match curve {
+        AccelerationCurve::Linear => initial_speed + (max_speed - initial_speed) * progress,
+        AccelerationCurve::Quadratic => {
+            initial_speed + (max_speed - initial_speed) * progress * progress
+        }
+        AccelerationCurve::Exponential => initial_speed * (max_speed / initial_speed).powf(progress),
+    }
+}
+
+/// Splits a mouse movement into the whole pixels to move now and the fraction to carry over.
+///
+/// # Arguments
+///
+/// * `delta` - The movement computed for this tick.
+/// * `remainder` - The fraction carried over from the previous ticks.
+///
+/// # Returns
+///
+/// The pixels to move and the new remainder, which is always less than a pixel.
+pub fn take_whole_pixels(delta: f32, remainder: f32) -> (i32, f32) {
+    let total = delta + remainder;
+    let pixels = total.trunc();
+    (pixels as i32, total - pixels)
+}
+
+/// Rounds a mouse movement to whole pixels, dropping what is left.
+///
+/// `MouseRounding::Accumulate` keeps the fraction with `take_whole_pixels`
+/// instead, and truncates here.
+pub fn round_pixels(delta: f32, rounding: MouseRounding) -> i32 {
+    match rounding {
+        MouseRounding::Accumulate | MouseRounding::Truncate => delta.trunc() as i32,
+        MouseRounding::Nearest => delta.round() as i32,
+        MouseRounding::HalfEven => delta.round_ties_even() as i32,
+    }
+}
+
+/// Clamps a stick axis value to `[-limit, limit]`.
+///
+/// Some drivers report values slightly outside of the expected range, which
+/// would skew the distance computations of the stick loops. Non-finite values,
+/// which some drivers report on disconnect, are centered instead.
+pub fn clamp_axis(value: f32, limit: f32) -> f32 {
+    if value.is_finite() { value.clamp(-limit, limit) } else { 0. }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_8, PI};
+
+    use super::*;
+
+    #[test]
+    fn test_apply_dead_zone() {
+        for shape in [DeadZoneShape::Radial, DeadZoneShape::Axial, DeadZoneShape::Scaled] {
+            assert_eq!(apply_dead_zone(0., 0., 0.2, shape), (0., 0.));
+            assert_eq!(apply_dead_zone(0.1, -0.1, 0.2, shape), (0., 0.));
+        }
+
+        let (x, y) = apply_dead_zone(0.6, 0.8, 0.5, DeadZoneShape::Radial);
+        assert!((x - 0.3).abs() < 1e-6 && (y - 0.4).abs() < 1e-6);
+        let (x, y) = apply_dead_zone(0.6, 0.8, 0.5, DeadZoneShape::Scaled);
+        assert!((x - 0.6).abs() < 1e-6 && (y - 0.8).abs() < 1e-6);
+
+        // Drift on one axis is removed without affecting the other
+        let (x, y) = apply_dead_zone(0.15, -0.9, 0.2, DeadZoneShape::Axial);
+        assert_eq!(x, 0.);
+        assert!((y + 0.7).abs() < 1e-6);
+        let (x, _) = apply_dead_zone(0.15, -0.9, 0.2, DeadZoneShape::Radial);
+        assert!(x > 0.);
+
+        // Just outside of the dead zone, the movement starts from zero
+        let (x, y) = apply_dead_zone(0.25, 0., 0.125, DeadZoneShape::Radial);
+        assert_eq!((x, y), (0.125, 0.));
+        let (x, y) = apply_dead_zone(0., -0.25, 0.125, DeadZoneShape::Axial);
+        assert_eq!((x, y), (0., -0.125));
+        let (x, _) = apply_dead_zone(0.25, 0., 0.125, DeadZoneShape::Scaled);
+        assert!((x - 0.125 / 0.875).abs() < 1e-6);
+
+        // A full push only reaches full length once scaled
+        assert_eq!(apply_dead_zone(1., 0., 0.125, DeadZoneShape::Radial), (0.875, 0.));
+        assert_eq!(apply_dead_zone(0., 1., 0.125, DeadZoneShape::Axial), (0., 0.875));
+        assert_eq!(apply_dead_zone(-1., 0., 0.125, DeadZoneShape::Scaled), (-1., 0.));
+    }
+
+    #[test]
+    fn test_gate_dead_zone() {
+        for shape in [DeadZoneShape::Radial, DeadZoneShape::Axial, DeadZoneShape::Scaled] {
+            assert_eq!(gate_dead_zone(0.1, -0.1, 0.2, shape), (0., 0.));
+            // Just outside of the dead zone, the movement is the whole push
+            assert_eq!(gate_dead_zone(0.25, 0., 0.125, shape), (0.25, 0.));
+        }
+
+        assert_eq!(gate_dead_zone(0.15, -0.9, 0.2, DeadZoneShape::Radial), (0.15, -0.9));
+        assert_eq!(gate_dead_zone(0.15, -0.9, 0.2, DeadZoneShape::Axial), (0., -0.9));
+    }
+
+    #[test]
+    fn test_apply_dead_zone_is_finite() {
+        // The center is where the shrink ratio would divide by zero, e.g. without a dead zone
+        for shape in [DeadZoneShape::Radial, DeadZoneShape::Axial, DeadZoneShape::Scaled] {
+            assert_eq!(apply_dead_zone(0., 0., 0., shape), (0., 0.));
+
+            for dead_zone in [0., 0.05, 0.5, 0.99] {
+                for (x, y) in [(0., 0.), (1e-30, 0.), (0., -1.), (0.7, 0.7), (1., 1.)] {
+                    let (x, y) = apply_dead_zone(x, y, dead_zone, shape);
+                    let (x, y) = apply_response_curve(x, y, 2.);
+                    assert!(x.is_finite() && y.is_finite(), "{shape:?} {dead_zone} {x} {y}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_response_curve() {
+        assert_eq!(apply_response_curve(0.3, -0.4, 1.), (0.3, -0.4));
+        assert_eq!(apply_response_curve(0., 0., 2.), (0., 0.));
+
+        // A half push is halved again, keeping its direction
+        let (x, y) = apply_response_curve(0.3, 0.4, 2.);
+        assert!((x - 0.15).abs() < 1e-6 && (y - 0.2).abs() < 1e-6);
+
+        // A full push is unchanged
+        let (x, y) = apply_response_curve(1., 0., 3.);
+        assert!((x - 1.).abs() < 1e-6 && y == 0.);
+    }
+
+    #[test]
+    fn test_normalize_diagonal() {
+        assert_eq!(normalize_diagonal(0.3, -0.4), (0.3, -0.4));
+        assert_eq!(normalize_diagonal(1., 0.), (1., 0.));
+
+        let (x, y) = normalize_diagonal(1., -1.);
+        assert!((x - FRAC_1_SQRT_2).abs() < 1e-6 && (y + FRAC_1_SQRT_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_absolute_position() {
+        assert_eq!(absolute_position(0., 0., 1921, 1081), (960, 540));
+        assert_eq!(absolute_position(-1., 1., 1920, 1080), (0, 0));
+        assert_eq!(absolute_position(1., -1., 1920, 1080), (1919, 1079));
+        assert_eq!(absolute_position(0.5, 0., 1921, 1081), (1440, 540));
+    }
+
+    #[test]
+    fn test_ramp_speed() {
+        for curve in [
+            AccelerationCurve::Linear,
+            AccelerationCurve::Quadratic,
+            AccelerationCurve::Exponential,
+        ] {
+            assert_eq!(ramp_speed(curve, 10., 20., 0, 30.), 10.);
+            assert!((ramp_speed(curve, 10., 20., 30, 30.) - 20.).abs() < 1e-4);
+            assert!((ramp_speed(curve, 10., 20., 100, 30.) - 20.).abs() < 1e-4);
+        }
+
+        assert_eq!(ramp_speed(AccelerationCurve::Linear, 10., 20., 15, 30.), 15.);
+        assert_eq!(ramp_speed(AccelerationCurve::Quadratic, 10., 20., 15, 30.), 12.5);
+        assert!((ramp_speed(AccelerationCurve::Exponential, 10., 40., 15, 30.) - 20.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_round_pixels() {
+        assert_eq!(round_pixels(1.7, MouseRounding::Truncate), 1);
+        assert_eq!(round_pixels(-1.7, MouseRounding::Truncate), -1);
+        assert_eq!(round_pixels(1.7, MouseRounding::Nearest), 2);
+        assert_eq!(round_pixels(2.5, MouseRounding::Nearest), 3);
+        assert_eq!(round_pixels(-0.5, MouseRounding::Nearest), -1);
+        assert_eq!(round_pixels(2.5, MouseRounding::HalfEven), 2);
+        assert_eq!(round_pixels(3.5, MouseRounding::HalfEven), 4);
+        assert_eq!(round_pixels(-0.5, MouseRounding::HalfEven), 0);
+    }
+
+    #[test]
+    fn test_take_whole_pixels() {
+        assert_eq!(take_whole_pixels(2.5, 0.), (2, 0.5));
+        assert_eq!(take_whole_pixels(0.25, 0.5), (0, 0.75));
+        assert_eq!(take_whole_pixels(0.5, 0.75), (1, 0.25));
+        assert_eq!(take_whole_pixels(-0.75, -0.5), (-1, -0.25));
+
+        // A slow push still moves the cursor, one pixel every few ticks
+        let mut remainder = 0.;
+        let mut moved = 0;
+        for _ in 0..8 {
+            let (pixels, new_remainder) = take_whole_pixels(0.25, remainder);
+            moved += pixels;
+            remainder = new_remainder;
+        }
+        assert_eq!(moved, 2);
+    }
+
+    #[test]
+    fn test_clamp_axis() {
+        assert_eq!(clamp_axis(0.5, 1.), 0.5);
+        assert_eq!(clamp_axis(-1., 1.), -1.);
+        assert_eq!(clamp_axis(1.02, 1.), 1.);
+        assert_eq!(clamp_axis(-1.3, 1.), -1.);
+        assert_eq!(clamp_axis(0.95, 0.9), 0.9);
+        assert_eq!(clamp_axis(f32::NAN, 1.), 0.);
+        assert_eq!(clamp_axis(f32::NEG_INFINITY, 1.), 0.);
+    }
+
+    #[test]
+    fn test_direction_4way_cardinals() {
+        for bias in [DiagonalBias::Vertical, DiagonalBias::Horizontal] {
+            assert_eq!(direction_4way(0., bias), StickDirection::Right);
+            assert_eq!(direction_4way(FRAC_PI_2, bias), StickDirection::Up);
+            assert_eq!(direction_4way(-FRAC_PI_2, bias), StickDirection::Down);
+            assert_eq!(direction_4way(PI, bias), StickDirection::Left);
+            assert_eq!(direction_4way(-PI, bias), StickDirection::Left);
+        }
+    }
+
+    #[test]
+    fn test_direction_4way_boundaries_vertical_bias() {
+        let bias = DiagonalBias::Vertical;
+        assert_eq!(direction_4way(FRAC_PI_4, bias), StickDirection::Up);
+        assert_eq!(direction_4way(3. * FRAC_PI_4, bias), StickDirection::Up);
+        assert_eq!(direction_4way(-FRAC_PI_4, bias), StickDirection::Down);
+        assert_eq!(direction_4way(-3. * FRAC_PI_4, bias), StickDirection::Down);
+    }
+
+    #[test]
+    fn test_direction_4way_boundaries_horizontal_bias() {
+        let bias = DiagonalBias::Horizontal;
+        assert_eq!(direction_4way(FRAC_PI_4, bias), StickDirection::Right);
+        assert_eq!(direction_4way(3. * FRAC_PI_4, bias), StickDirection::Left);
+        assert_eq!(direction_4way(-FRAC_PI_4, bias), StickDirection::Right);
+        assert_eq!(direction_4way(-3. * FRAC_PI_4, bias), StickDirection::Left);
+    }
+
+    #[test]
+    fn test_direction_8way() {
+        assert_eq!(direction_8way(0.), StickDirection::Right);
+        assert_eq!(direction_8way(FRAC_PI_4), StickDirection::UpRight);
+        assert_eq!(direction_8way(FRAC_PI_2), StickDirection::Up);
+        assert_eq!(direction_8way(3. * FRAC_PI_4), StickDirection::UpLeft);
+        assert_eq!(direction_8way(PI), StickDirection::Left);
+        assert_eq!(direction_8way(-PI), StickDirection::Left);
+        assert_eq!(direction_8way(-3. * FRAC_PI_4), StickDirection::DownLeft);
+        assert_eq!(direction_8way(-FRAC_PI_2), StickDirection::Down);
+        assert_eq!(direction_8way(-FRAC_PI_4), StickDirection::DownRight);
+
+        // Sectors span 22.5° on each side of their direction
+        assert_eq!(direction_8way(FRAC_PI_8 - 0.01), StickDirection::Right);
+        assert_eq!(direction_8way(FRAC_PI_8 + 0.01), StickDirection::UpRight);
+    }
+
+    #[test]
+    fn test_pushed_direction() {
+        let settings = DirectionalSettings {
+            dead_zone: 0.125,
+            trigger_zone: 0.5,
+            hysteresis: 0.25,
+            directions: 4,
+            diagonal_bias: DiagonalBias::Vertical,
+        };
+
+        // Each cardinal direction fires once past the trigger zone
+        assert_eq!(pushed_direction(0.5, 0., false, &settings), Some(StickDirection::Right));
+        assert_eq!(pushed_direction(0., 0.75, false, &settings), Some(StickDirection::Up));
+        assert_eq!(pushed_direction(-1., 0., false, &settings), Some(StickDirection::Left));
+        assert_eq!(pushed_direction(0., -0.5, false, &settings), Some(StickDirection::Down));
+        assert_eq!(pushed_direction(0.375, 0., false, &settings), None);
+        assert_eq!(pushed_direction(0., 0., false, &settings), None);
+
+        // Between two sectors, the bias decides
+        assert_eq!(pushed_direction(0.5, 0.5, false, &settings), Some(StickDirection::Up));
+        let horizontal = DirectionalSettings { diagonal_bias: DiagonalBias::Horizontal, ..settings };
+        assert_eq!(pushed_direction(-0.5, 0.5, false, &horizontal), Some(StickDirection::Left));
+        let eight = DirectionalSettings { directions: 8, ..settings };
+        assert_eq!(pushed_direction(0.5, 0.5, false, &eight), Some(StickDirection::UpRight));
+        assert_eq!(pushed_direction(0.5, 0.125, false, &eight), Some(StickDirection::Right));
+
+        // A held direction stays held above the hysteresis, following the stick
+        assert_eq!(pushed_direction(0.375, 0., true, &settings), Some(StickDirection::Right));
+        assert_eq!(pushed_direction(0., -0.375, true, &settings), Some(StickDirection::Down));
+        assert_eq!(pushed_direction(0.25, 0., true, &settings), None);
+
+        // The dead zone releases it whatever the hysteresis
+        let wide = DirectionalSettings { hysteresis: 0.5, ..settings };
+        assert_eq!(pushed_direction(0.25, 0., true, &wide), Some(StickDirection::Right));
+        assert_eq!(pushed_direction(0.125, 0., true, &wide), None);
+    }
+
+    #[test]
+    fn test_direction_4way_diagonal_push() {
+        // A perfect diagonal push computed from the stick coordinates
+        assert_eq!(
+            direction_4way(1f32.atan2(1.), DiagonalBias::Vertical),
+            StickDirection::Up
+        );
+        assert_eq!(
+            direction_4way((-1f32).atan2(-1.), DiagonalBias::Horizontal),
+            StickDirection::Left
+        );
+    }
+}
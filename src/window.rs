@@ -0,0 +1,94 @@
+use crate::config::WindowMatcher;
+
+/// The focused window, as matched by the `when_window` condition of a binding.
+#[derive(Debug)]
+pub struct ForegroundWindow {
+    /// File name of the executable owning the window, e.g. `game.exe`.
+    pub process: String,
+    pub title: String,
+}
+
+#[cfg(target_os = "windows")]
+#[link(name = "user32")]
+unsafe extern "system" {
+    fn GetForegroundWindow() -> *mut std::ffi::c_void;
+    fn GetWindowTextW(window: *mut std::ffi::c_void, text: *mut u16, max_count: i32) -> i32;
+    fn GetWindowThreadProcessId(window: *mut std::ffi::c_void, process_id: *mut u32) -> u32;
+}
+
+#[cfg(target_os = "windows")]
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn OpenProcess(desired_access: u32, inherit_handle: i32, process_id: u32) -> *mut std::ffi::c_void;
+    fn QueryFullProcessImageNameW(process: *mut std::ffi::c_void, flags: u32, name: *mut u16, size: *mut u32)
+    -> i32;
+    fn CloseHandle(handle: *mut std::ffi::c_void) -> i32;
+}
+
+#[cfg(target_os = "windows")]
+const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+
+/// Returns an identifier of the focused window, or `None` if there is none, e.g. on the lock screen.
+#[cfg(target_os = "windows")]
+pub fn foreground_window_id() -> Option<usize> {
+    let window = unsafe { GetForegroundWindow() };
+    (!window.is_null()).then_some(window as usize)
+}
+
+/// Returns the focused window, or `None` if there is none, e.g. on the lock screen.
+#[cfg(target_os = "windows")]
+pub fn foreground_window() -> Option<ForegroundWindow> {
+    unsafe {
+        let window = GetForegroundWindow();
+        if window.is_null() {
+            return None;
+        }
+
+        let mut title = [0; 512];
+        let title_len = GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32);
+        let title = String::from_utf16_lossy(&title[..title_len.max(0) as usize]);
+
+        let mut process_id = 0;
+        GetWindowThreadProcessId(window, &mut process_id);
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process_id);
+        let mut path = [0; 1024];
+        let mut path_len = path.len() as u32;
+        let is_path_read =
+            !process.is_null() && QueryFullProcessImageNameW(process, 0, path.as_mut_ptr(), &mut path_len) != 0;
+        if !process.is_null() {
+            CloseHandle(process);
+        }
+
+        let path = if is_path_read {
+            String::from_utf16_lossy(&path[..path_len as usize])
+        } else {
+            String::new()
+        };
+        let process = path.rsplit(['\\', '/']).next().unwrap_or_default().to_string();
+        Some(ForegroundWindow { process, title })
+    }
+}
+
+/// Returns an identifier of the focused window.
+///
+/// Windows cannot be queried on this platform, so this is always `None`.
+#[cfg(not(target_os = "windows"))]
+pub fn foreground_window_id() -> Option<usize> {
+    None
+}
+
+/// Returns the focused window.
+///
+/// Windows cannot be queried on this platform, so this is always `None`.
+#[cfg(not(target_os = "windows"))]
+pub fn foreground_window() -> Option<ForegroundWindow> {
+    None
+}
+
+/// Checks whether the focused window meets a `when_window` condition.
+///
+/// Where the focused window is unknown, the condition is considered met, so
+/// that bindings keep working on platforms without window detection.
+pub fn is_focused(matcher: &WindowMatcher) -> bool {
+    foreground_window().is_none_or(|window| matcher.matches(&window.process, &window.title))
+}